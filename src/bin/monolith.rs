@@ -1,5 +1,4 @@
 extern crate docopt;
-extern crate libc;
 extern crate libintermix;
 #[macro_use]
 extern crate log;
@@ -7,25 +6,45 @@ extern crate log4rs;
 extern crate rustc_serialize;
 extern crate term;
 extern crate termios;
+extern crate uuid;
 
-use libc::c_ushort;
 use std::io;
 use std::os::unix::io::RawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
 
 const USAGE: &'static str = "
 intermix - a terminal emulator multiplexer
 
 Usage:
-intermix [<command>...]
+intermix [options] [-s PATH]... [<command>...]
 intermix -h | --help
 
 Options:
--h --help      Show this screen
+-h --help          Show this screen
+-a --attach        Attach to programs on a running server. Fails if no server is running
+-b --bell          Ring this terminal's bell when a program rings its bell
+-c --clipboard     Copy to this terminal's clipboard when a program sets the clipboard or text is
+                   yanked in copy mode. The terminal has to support OSC 52
+--no-mouse         Leave the mouse to the terminal, instead of clicks focusing panes and the mouse
+                   going to programs that ask for it
+-s --socket PATH   Connect to the server on this unix socket instead of the default. Give it more
+                   than once to show programs from several servers side by side
+-t --title         Set this terminal's title to the selected program's title
 ";
 
 #[derive(Debug, RustcDecodable)]
 struct Args {
     arg_command: Vec<String>,
+    flag_attach: bool,
+    flag_bell: bool,
+    flag_clipboard: bool,
+    flag_no_mouse: bool,
+    flag_socket: Vec<String>,
+    flag_title: bool,
 }
 
 fn setup_logging() {
@@ -42,53 +61,121 @@ fn parse_args() -> Args {
         .unwrap_or_else(|e| e.exit())
 }
 
+/// Connect to the server listening at path. If there isn't one, start a daemon server there
+/// first. The server binary is expected to live next to this one.
+fn connect_to_server(path: &Path) -> UnixStream {
+    if let Ok(stream) = UnixStream::connect(path) {
+        return stream;
+    }
+
+    let server_bin = std::env::current_exe()
+                         .expect("couldn't find current exe")
+                         .with_file_name("intermix-server");
+    info!("starting server {:?}", server_bin);
+    let status = process::Command::new(&server_bin)
+                     .arg("--daemon")
+                     .arg("--socket")
+                     .arg(path)
+                     .status()
+                     .unwrap_or_else(|e| panic!("couldn't start {:?}: {}", server_bin, e));
+    if !status.success() {
+        panic!("{:?} exited with {}", server_bin, status);
+    }
+
+    // the daemon needs a moment to bind
+    for _ in 0..50 {
+        if let Ok(stream) = UnixStream::connect(path) {
+            return stream;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    panic!("couldn't connect to server at {:?}", path);
+}
+
 fn main() {
     setup_logging();
-    parse_args();
-    set_raw_mode(0);
+    let args = parse_args();
+
+    let socket_paths: Vec<PathBuf> = if args.flag_socket.is_empty() {
+        vec![libintermix::transport::default_socket_path()]
+    } else {
+        args.flag_socket.iter().map(PathBuf::from).collect()
+    };
+
+    // Connect to everything before touching the terminal, so failures are printed normally
+    let client_id = uuid::Uuid::new_v4().to_hyphenated_string();
+    let connections: Vec<(UnixStream, String)> = socket_paths.iter()
+                                                             .map(|path| {
+                                                                 connect(path,
+                                                                         args.flag_attach,
+                                                                         &client_id)
+                                                             })
+                                                             .collect();
 
-    let (server_tx, server_handle) = libintermix::server::Server::spawn();
+    set_raw_mode(0);
 
 
-    let tty_ioctl_config: libintermix::client::TtyIoCtlConfig;
-    unsafe {
-        let mut size: WinSize = std::mem::zeroed();
-        libc::ioctl(1, TIOCGWINSZ, &mut size);
-        tty_ioctl_config = libintermix::client::TtyIoCtlConfig {
-            rows: size.rows as usize,
-            cols: size.cols as usize,
-            ..Default::default()
-        };
-    }
-    let (client_tx, _) = libintermix::client::Client::spawn(io::stdin(),
-                                                            io::stdout(),
-                                                            tty_ioctl_config);
-
-    client_tx.send(libintermix::client::ClientMsg::ServerAdd {
-                 server: libintermix::client::servers::Server {
-                     id: "some server".to_string(),
-                     tx: server_tx.clone(),
-                     programs: vec![],
-                 },
-             })
-             .expect("sending client message failed");
-
-    server_tx.send(libintermix::server::ServerMsg::ClientAdd {
-                 client: libintermix::server::Client {
-                     id: "some client".to_string(),
-                     tx: client_tx.clone(),
-                 },
-             })
-             .expect("sending server message failed");
-
-    let threads = vec![server_handle];
-    for thr in threads {
-        thr.join().expect("thread wouldn't join");
+    let size = libintermix::client::winch_worker::tty_size(1).expect("couldn't get tty size");
+    let tty_ioctl_config = libintermix::client::TtyIoCtlConfig {
+        rows: size.height,
+        cols: size.width,
+        ..Default::default()
+    };
+    let config = libintermix::client::ClientConfig {
+        forward_clipboard: args.flag_clipboard,
+        mouse: !args.flag_no_mouse,
+        set_title: args.flag_title,
+        forward_bell: args.flag_bell,
+    };
+    let (client_tx, client) = libintermix::client::Client::spawn(io::stdin(),
+                                                                 io::stdout(),
+                                                                 tty_ioctl_config,
+                                                                 config);
+    libintermix::client::winch_worker::WinchWorker::spawn(1, client_tx.clone())
+        .expect("couldn't watch for terminal resizes");
+
+    for (stream, server_id) in connections {
+        let reader = stream.try_clone().expect("couldn't clone server stream");
+
+        client_tx.send(libintermix::client::ClientMsg::ServerAdd {
+                     server: libintermix::client::servers::Server::new(
+                         &server_id,
+                         &client_id,
+                         libintermix::transport::Tx::socket(stream)),
+                 })
+                 .expect("sending client message failed");
+
+        let on_close = libintermix::client::ClientMsg::ServerRemove { server_id: server_id };
+        libintermix::transport::spawn_socket_reader(reader, client_tx.clone(), Some(on_close));
     }
 
+    client.join();
+
     set_cooked_mode(0);
 }
 
+/// Connect and say hello to the server at path. Returns the stream and the server's id. Exits if
+/// that isn't possible.
+fn connect(path: &Path, attach_only: bool, client_id: &str) -> (UnixStream, String) {
+    let mut stream = if attach_only {
+        UnixStream::connect(path).unwrap_or_else(|e| {
+            println!("no server to attach to at {:?}: {}", path, e);
+            process::exit(1);
+        })
+    } else {
+        connect_to_server(path)
+    };
+
+    match libintermix::protocol::handshake(&mut stream, client_id) {
+        Ok(hello) => (stream, hello.id),
+        Err(e) => {
+            println!("couldn't talk to the server at {:?}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
 // https://github.com/ruby/ruby/blob/trunk/ext/io/console/console.c
 fn set_raw_mode(fd: RawFd) {
     let mut t = termios::Termios::from_fd(fd).expect("termios wouldn't initialize with fd");
@@ -104,17 +191,3 @@ fn set_cooked_mode(fd: RawFd) {
                  termios::ICANON | termios::ISIG | termios::IEXTEN;
     termios::tcsetattr(fd, termios::TCSANOW, &t).expect("tcsetattr to cooked mode failed");
 }
-
-#[derive(Debug)]
-#[repr(C)]
-struct WinSize {
-    rows: c_ushort,
-    cols: c_ushort,
-    x_pixels: c_ushort,
-    y_pixels: c_ushort,
-}
-
-#[cfg(any(target_os = "macos", target_os = "freebsd"))]
-const TIOCGWINSZ: libc::c_ulong = 0x40087468;
-#[cfg(any(target_os = "linux", target_os = "android"))]
-const TIOCGWINSZ: libc::c_ulong = 0x5413;
@@ -0,0 +1,102 @@
+extern crate docopt;
+extern crate libc;
+extern crate libintermix;
+#[macro_use]
+extern crate log;
+extern crate log4rs;
+extern crate rustc_serialize;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+const USAGE: &'static str = "
+intermix-server - runs programs for intermix clients
+
+Usage:
+intermix-server [options]
+intermix-server -h | --help
+
+Options:
+-h --help          Show this screen
+-d --daemon        Detach from the terminal and run in the background
+-s --socket PATH   Listen on this unix socket instead of the default
+-n --name NAME     What clients call this server. Defaults to the hostname. Clients connected to
+                   several servers need each one to have a different name
+-g --kill-grace SECS  How long a program has after SIGTERM before it gets SIGKILL [default: 5]
+-l --scrollback LINES  How many lines each program keeps once they scroll off [default: 10000]
+-q --silence SECS  How long a program has to be quiet to count as silent [default: 10]
+-t --term NAME     The TERM programs are started with. Defaults to intermix if its terminfo entry
+                   is installed, otherwise xterm-256color
+";
+
+#[derive(Debug, RustcDecodable)]
+struct Args {
+    flag_daemon: bool,
+    flag_socket: Option<String>,
+    flag_name: Option<String>,
+    flag_kill_grace: u64,
+    flag_scrollback: usize,
+    flag_silence: u64,
+    flag_term: Option<String>,
+}
+
+fn setup_logging() {
+    log4rs::init_file(&std::env::current_dir()
+                           .expect("couldn't get current dir")
+                           .join("log4rs.yaml"),
+                      Default::default())
+        .expect("log4rs couldn't init");
+}
+
+fn parse_args() -> Args {
+    docopt::Docopt::new(USAGE)
+        .and_then(|d| d.decode())
+        .unwrap_or_else(|e| e.exit())
+}
+
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let result = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if result != 0 {
+        return "localhost".to_string();
+    }
+
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+fn main() {
+    let args = parse_args();
+
+    if args.flag_daemon {
+        // keep the working directory so log4rs.yaml can still be found
+        if unsafe { libc::daemon(1, 0) } != 0 {
+            panic!("daemon failed: {}", std::io::Error::last_os_error());
+        }
+    }
+
+    setup_logging();
+
+    let socket_path = args.flag_socket
+                          .map(PathBuf::from)
+                          .unwrap_or_else(libintermix::transport::default_socket_path);
+
+    let name = args.flag_name.unwrap_or_else(hostname);
+
+    let config = libintermix::server::ServerConfig {
+        kill_grace_period: Duration::from_secs(args.flag_kill_grace),
+        scrollback_lines: args.flag_scrollback,
+        silence_period: Duration::from_secs(args.flag_silence),
+        term: args.flag_term.unwrap_or_else(libintermix::server::default_term),
+        socket_path: Some(socket_path.clone()),
+    };
+
+    let (server_tx, server_handle) = libintermix::server::Server::spawn(&name, config);
+
+    libintermix::server::SocketListener::spawn(&socket_path, &name, server_tx)
+        .unwrap_or_else(|e| panic!("couldn't listen on {:?}: {}", socket_path, e));
+    let _socket_file = libintermix::server::SocketFile::new(&socket_path);
+
+    server_handle.join().expect("thread wouldn't join");
+    info!("server exiting");
+}
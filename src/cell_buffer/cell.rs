@@ -1,4 +1,6 @@
+use std::str;
 use vterm_sys::{ScreenCell, ColorPalette, ColorRGB};
+use super::palette::palette_rgb;
 
 // TODO:
 //
@@ -11,7 +13,7 @@ pub struct Cell {
     pub blink: bool,
     pub bold: bool,
 
-    // TODO: This should be a fixed size array, and method should exist to return slices.
+    /// utf8 for a base char followed by any combining chars drawn over it
     pub chars: Vec<u8>,
 
     pub dhl: u8, // On a DECDHL line (1=top 2=bottom)
@@ -24,6 +26,7 @@ pub struct Cell {
     pub reverse: bool,
     pub strike: bool,
     pub underline: u8, // 0 to 3
+    /// 2 for a wide char, whose right half is the next cell. That cell has a width of 0.
     pub width: u8,
 }
 
@@ -31,11 +34,7 @@ impl Cell {
     pub fn new() -> Cell {
         Cell {
             bg_palette: 0,
-            bg_rgb: ColorRGB {
-                red: 5,
-                green: 5,
-                blue: 5,
-            },
+            bg_rgb: palette_rgb(0),
             blink: Default::default(),
             bold: Default::default(),
             chars: Default::default(),
@@ -43,17 +42,13 @@ impl Cell {
             dirty: Default::default(),
             dwl: Default::default(),
             fg_palette: 7,
-            fg_rgb: ColorRGB {
-                red: 230,
-                green: 230,
-                blue: 230,
-            },
+            fg_rgb: palette_rgb(7),
             font: Default::default(),
             italic: Default::default(),
             reverse: Default::default(),
             strike: Default::default(),
             underline: Default::default(),
-            width: Default::default(),
+            width: 1,
         }
 
     }
@@ -63,7 +58,7 @@ impl Cell {
         self.bg_rgb = vterm_cell.bg_rgb.clone();
         self.blink = vterm_cell.attrs.blink;
         self.bold = vterm_cell.attrs.bold;
-        self.chars = vterm_cell.chars.clone();
+        self.chars = chars_from_vterm(&vterm_cell.chars);
         self.dhl = vterm_cell.attrs.dhl;
         self.dwl = vterm_cell.attrs.dwl;
         self.fg_palette = vterm_cell.fg_palette;
@@ -78,11 +73,7 @@ impl Cell {
 
     pub fn clear(&mut self) {
         self.bg_palette = 0;
-        self.bg_rgb = ColorRGB {
-            red: 5,
-            green: 5,
-            blue: 5,
-        };
+        self.bg_rgb = palette_rgb(0);
         self.blink = Default::default();
         self.bold = Default::default();
         self.chars.clear();
@@ -90,16 +81,103 @@ impl Cell {
         self.dirty = true;
         self.dwl = Default::default();
         self.fg_palette = 7;
-        self.fg_rgb = ColorRGB {
-                red: 230,
-                green: 230,
-                blue: 230,
-            };
+        self.fg_rgb = palette_rgb(7);
         self.font = Default::default();
         self.italic = Default::default();
         self.reverse = Default::default();
         self.strike = Default::default();
         self.underline = Default::default();
-        self.width = Default::default();
+        self.width = 1;
+    }
+
+    /// The right half of a wide char, which is drawn along with the cell before it
+    pub fn is_continuation(&self) -> bool {
+        self.width == 0
+    }
+
+    /// A row's cells are fixed up so every wide char is followed by a continuation, and only wide
+    /// chars are. Cells that change are marked dirty.
+    pub fn mark_continuations(row: &mut [Cell]) {
+        for x in 0..row.len() {
+            let after_wide = x > 0 && row[x - 1].width == 2;
+            let cell = &mut row[x];
+
+            if after_wide && (!cell.is_continuation() || !cell.chars.is_empty()) {
+                cell.chars.clear();
+                cell.width = 0;
+                cell.dirty = true;
+            } else if !after_wide && cell.is_continuation() {
+                cell.width = 1;
+                cell.dirty = true;
+            }
+        }
+    }
+}
+
+/// vterm has room for several codepoints per cell, so a char and its combining chars stay
+/// together. Anything from a nul on is unused room. The right half of a wide char holds a marker
+/// that isn't a char at all, so any bytes that aren't utf8 are dropped.
+fn chars_from_vterm(bytes: &[u8]) -> Vec<u8> {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    match str::from_utf8(&bytes[..end]) {
+        Ok(s) => s.as_bytes().to_vec(),
+        Err(e) => bytes[..e.valid_up_to()].to_vec(),
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use vterm_sys::ScreenCell;
+
+    fn cell(chars: &str, width: u8) -> Cell {
+        let mut cell = Cell::new();
+        cell.chars = chars.to_string().into_bytes();
+        cell.width = width;
+        cell
+    }
+
+    #[test]
+    fn it_keeps_combining_chars_with_their_base() {
+        let mut vterm_cell: ScreenCell = Default::default();
+        vterm_cell.chars = "e\u{301}\0\0".to_string().into_bytes();
+
+        let mut cell = Cell::new();
+        cell.update_from_vterm_cell(&vterm_cell);
+
+        assert_eq!(cell.chars, "e\u{301}".to_string().into_bytes());
+    }
+
+    #[test]
+    fn it_drops_bytes_that_arent_chars() {
+        let mut vterm_cell: ScreenCell = Default::default();
+        vterm_cell.chars = vec![0xff, 0xff, 0xff, 0xff];
+
+        let mut cell = Cell::new();
+        cell.update_from_vterm_cell(&vterm_cell);
+
+        assert!(cell.chars.is_empty());
+    }
+
+    #[test]
+    fn it_marks_the_cell_after_a_wide_char_as_a_continuation() {
+        let mut row = vec![cell("中", 2), cell("?", 1), cell("a", 1)];
+
+        Cell::mark_continuations(&mut row);
+
+        assert!(row[1].is_continuation());
+        assert!(row[1].chars.is_empty());
+        assert!(row[1].dirty);
+        assert!(!row[2].is_continuation());
+    }
+
+    #[test]
+    fn it_clears_continuations_that_arent_after_a_wide_char() {
+        let mut row = vec![cell("a", 1), cell("", 0)];
+
+        Cell::mark_continuations(&mut row);
+
+        assert_eq!(row[1].width, 1);
+        assert!(row[1].dirty);
     }
 }
@@ -26,8 +26,35 @@ impl CellBuffer {
         self.cells.iter().zip(self.rect.positions())
     }
 
-    pub fn resize(&mut self) {
-        panic!("not implemented")
+    pub fn size(&self) -> &Size {
+        &self.rect.size
+    }
+
+    /// Cells that are in both the old and new size keep their contents. Every cell is marked
+    /// dirty, since the terminal underneath has likely been resized too.
+    pub fn resize(&mut self, size: Size) {
+        let mut cells = vec![Cell::new(); size.width * size.height];
+        let new_rect = Rect::new(Pos::new(0,0), size);
+
+        for (cell, pos) in cells.iter_mut().zip(new_rect.positions()) {
+            if self.rect.contains(&pos) {
+                *cell = self.cells[CellBuffer::pos_to_index(&pos, self.rect.size.width)].clone();
+            }
+            cell.dirty = true;
+        }
+
+        self.cells = cells;
+        self.rect = new_rect;
+    }
+
+    /// Makes sure every wide char is followed by its continuation. See `Cell::mark_continuations`.
+    pub fn mark_continuations(&mut self) {
+        if self.rect.size.width == 0 {
+            return;
+        }
+        for row in self.cells.chunks_mut(self.rect.size.width) {
+            Cell::mark_continuations(row);
+        }
     }
 
     // don't need this yet
@@ -53,3 +80,33 @@ impl IndexMut<Pos> for CellBuffer {
         self.cells.index_mut(CellBuffer::pos_to_index(&pos, self.rect.size.width))
     }
 }
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use vterm_sys::{Size, Pos};
+
+    #[test]
+    fn resize_keeps_cells_that_still_fit() {
+        let mut buffer = CellBuffer::new(Size::new(3, 2));
+        buffer[Pos::new(1, 1)].chars = vec![b'a'];
+        buffer[Pos::new(2, 0)].chars = vec![b'b'];
+
+        buffer.resize(Size::new(2, 3));
+
+        assert_eq!(*buffer.size(), Size::new(2, 3));
+        assert_eq!(buffer[Pos::new(1, 1)].chars, vec![b'a']);
+        assert!(buffer.iter().all(|pair| pair.0.dirty));
+        assert!(buffer.iter().all(|pair| pair.0.chars != vec![b'b']));
+    }
+
+    #[test]
+    fn wide_chars_dont_continue_onto_the_next_row() {
+        let mut buffer = CellBuffer::new(Size::new(2, 2));
+        buffer[Pos::new(1, 0)].width = 2;
+
+        buffer.mark_continuations();
+
+        assert!(!buffer[Pos::new(0, 1)].is_continuation());
+    }
+}
@@ -50,6 +50,8 @@
 
 mod cell;
 mod cell_buffer;
+mod palette;
 
 pub use self::cell::*;
 pub use self::cell_buffer::*;
+pub use self::palette::*;
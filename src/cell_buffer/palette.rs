@@ -0,0 +1,81 @@
+use vterm_sys::{ColorPalette, ColorRGB};
+
+/// libvterm's 16 ansi colors
+const ANSI_COLORS: [(u8, u8, u8); 16] = [(0, 0, 0),
+                                         (224, 0, 0),
+                                         (0, 224, 0),
+                                         (224, 224, 0),
+                                         (0, 0, 224),
+                                         (224, 0, 224),
+                                         (0, 224, 224),
+                                         (224, 224, 224),
+                                         (128, 128, 128),
+                                         (255, 64, 64),
+                                         (64, 255, 64),
+                                         (255, 255, 64),
+                                         (64, 64, 255),
+                                         (255, 64, 255),
+                                         (64, 255, 255),
+                                         (255, 255, 255)];
+
+/// The levels of each channel in libvterm's 6x6x6 color cube
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x33, 0x66, 0x99, 0xcc, 0xff];
+
+/// libvterm's grey ramp
+const GREY_LEVELS: [u8; 24] = [0x00, 0x0b, 0x16, 0x21, 0x2c, 0x37, 0x42, 0x4d, 0x58, 0x63, 0x6e,
+                               0x79, 0x85, 0x90, 0x9b, 0xa6, 0xb1, 0xbc, 0xc7, 0xd2, 0xdd, 0xe8,
+                               0xf3, 0xff];
+
+/// The color vterm uses for a palette index. vterm only gives cells their rgb, so a cell whose rgb
+/// is its palette index's color is taken to be using the palette, and anything else to be set
+/// directly.
+pub fn palette_rgb(index: ColorPalette) -> ColorRGB {
+    let i = index as usize;
+    let (red, green, blue) = if i < 16 {
+        ANSI_COLORS[i]
+    } else if i < 232 {
+        let i = i - 16;
+        (CUBE_LEVELS[i / 36], CUBE_LEVELS[(i / 6) % 6], CUBE_LEVELS[i % 6])
+    } else {
+        let level = GREY_LEVELS[i - 232];
+        (level, level, level)
+    };
+
+    ColorRGB {
+        red: red,
+        green: green,
+        blue: blue,
+    }
+}
+
+pub fn is_palette_color(index: ColorPalette, rgb: &ColorRGB) -> bool {
+    palette_rgb(index) == *rgb
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use vterm_sys::ColorRGB;
+
+    #[test]
+    fn it_has_the_ansi_colors_cube_and_greys() {
+        assert_eq!(palette_rgb(1),
+                   ColorRGB {
+                       red: 224,
+                       green: 0,
+                       blue: 0,
+                   });
+        assert_eq!(palette_rgb(16 + 36 * 5 + 6 * 2 + 1),
+                   ColorRGB {
+                       red: 0xff,
+                       green: 0x66,
+                       blue: 0x33,
+                   });
+        assert_eq!(palette_rgb(255),
+                   ColorRGB {
+                       red: 0xff,
+                       green: 0xff,
+                       blue: 0xff,
+                   });
+    }
+}
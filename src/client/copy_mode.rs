@@ -0,0 +1,697 @@
+use cell_buffer::{Cell, palette_rgb};
+use regex::{self, Regex};
+use std::cmp;
+use vterm_sys::{Pos, ScreenCell, Size};
+
+const CTRL_B: u8 = 2u8;
+const CTRL_D: u8 = 4u8;
+const CTRL_F: u8 = 6u8;
+const CTRL_U: u8 = 0x15u8;
+
+/// A pattern being looked for in the lines, and where it was found
+struct Search {
+    pattern: String,
+    is_regex: bool,
+    is_forward: bool,
+    /// where the cursor was when the search started, to go back to if it is cancelled
+    origin: Pos,
+    /// the line, first column and the column after the last of each match, in order
+    matches: Vec<(usize, usize, usize)>,
+    /// the pattern compiled, kept so lines fetched later can be searched on their own
+    regex: Option<Regex>,
+    is_valid: bool,
+}
+
+/// Moves a cursor over a program's scrollback and screen with vi keys, and selects text to yank.
+///
+/// It works on copies of the program's cells, so drawing the cursor and selection leaves the
+/// program's screen alone. Lines are counted from the oldest scrollback line that has been
+/// fetched, which means they shift down when older ones arrive.
+pub struct CopyMode {
+    pub wrap_name: String,
+    /// scrollback lines, oldest first, followed by the screen's lines
+    lines: Vec<Vec<Cell>>,
+    /// how many scrollback lines have been fetched, out of how many the server has
+    scrollback_fetched: usize,
+    scrollback_total: usize,
+    is_fetching: bool,
+    size: Size,
+    cursor: Pos,
+    /// the line shown at the top of the pane
+    top: usize,
+    /// where the selection started, if one has been
+    mark: Option<Pos>,
+    search: Option<Search>,
+}
+
+impl CopyMode {
+    /// The cursor starts on the bottom line of the screen. Scrollback is expected to be on its way.
+    pub fn new(wrap_name: &str, screen_lines: Vec<Vec<Cell>>, size: Size) -> CopyMode {
+        let mut copy_mode = CopyMode {
+            wrap_name: wrap_name.to_string(),
+            lines: screen_lines,
+            scrollback_fetched: 0,
+            scrollback_total: 0,
+            is_fetching: true,
+            size: size,
+            cursor: Pos::new(0, 0),
+            top: 0,
+            mark: None,
+            search: None,
+        };
+
+        let last_line = copy_mode.last_line();
+        copy_mode.move_to(0, last_line as isize);
+        copy_mode
+    }
+
+    /// Scrollback lines, oldest first, that are older than any already held
+    pub fn add_scrollback(&mut self, lines: Vec<Vec<ScreenCell>>, total: usize) {
+        let num_lines = lines.len();
+        let mut new_lines: Vec<Vec<Cell>> = lines.iter().map(|l| cells_from_vterm(l)).collect();
+        new_lines.extend(self.lines.drain(..));
+        self.lines = new_lines;
+
+        self.cursor.y += num_lines;
+        self.top += num_lines;
+        if let Some(ref mut mark) = self.mark {
+            mark.y += num_lines;
+        }
+
+        self.scrollback_fetched += num_lines;
+        self.scrollback_total = total;
+        self.is_fetching = false;
+
+        // only the new lines need searching, the rest just move down
+        if let Some(ref mut search) = self.search {
+            search.origin.y += num_lines;
+            let mut matches = match search.regex {
+                Some(ref regex) => find_in_lines(regex, &self.lines[..num_lines]),
+                None => vec![],
+            };
+            for m in search.matches.iter_mut() {
+                m.0 += num_lines;
+            }
+            matches.extend(search.matches.drain(..));
+            search.matches = matches;
+        }
+    }
+
+    /// When the cursor reaches the oldest line held and the server has older ones, returns where
+    /// to fetch from. While searching, all of it is wanted. Only one fetch is asked for at a time.
+    pub fn wants_scrollback(&mut self) -> Option<usize> {
+        if self.is_fetching || (self.cursor.y > 0 && self.search.is_none()) ||
+           self.scrollback_fetched >= self.scrollback_total {
+            return None;
+        }
+        self.is_fetching = true;
+        Some(self.scrollback_fetched)
+    }
+
+    pub fn size(&self) -> &Size {
+        &self.size
+    }
+
+    /// The pane changed size, so keep the cursor in view
+    pub fn resize(&mut self, size: Size) {
+        self.size = size;
+        let (x, y) = (self.cursor.x as isize, self.cursor.y as isize);
+        self.move_to(x, y);
+    }
+
+    /// Returns false for keys that don't mean anything in copy mode
+    pub fn handle_key(&mut self, key: u8) -> bool {
+        let (x, y) = (self.cursor.x as isize, self.cursor.y as isize);
+        let half_page = cmp::max(self.size.height / 2, 1) as isize;
+        let page = cmp::max(self.size.height, 1) as isize;
+
+        match key {
+            b'h' => self.move_to(x - 1, y),
+            b'l' => self.move_to(x + 1, y),
+            b'j' => self.move_to(x, y + 1),
+            b'k' => self.move_to(x, y - 1),
+            b'0' => self.move_to(0, y),
+            b'$' => {
+                let end = self.line_len(self.cursor.y) as isize - 1;
+                self.move_to(end, y)
+            }
+            b'g' => self.move_to(0, 0),
+            b'G' => {
+                let last_line = self.last_line() as isize;
+                self.move_to(0, last_line)
+            }
+            b'w' => self.word_forward(),
+            b'b' => self.word_back(),
+            CTRL_U => self.move_to(x, y - half_page),
+            CTRL_D => self.move_to(x, y + half_page),
+            CTRL_B => self.move_to(x, y - page),
+            CTRL_F => self.move_to(x, y + page),
+            b'n' => self.search_again(true),
+            b'N' => self.search_again(false),
+            b'v' => {
+                self.mark = match self.mark {
+                    Some(_) => None,
+                    None => Some(self.cursor.clone()),
+                };
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// The cells to show in the pane, row by row. The cursor and selection are in reverse video.
+    pub fn render(&self) -> Vec<Cell> {
+        let mut cells = Vec::with_capacity(self.size.width * self.size.height);
+        for row in 0..self.size.height {
+            let y = self.top + row;
+            for x in 0..self.size.width {
+                let mut cell = self.lines
+                                   .get(y)
+                                   .and_then(|line| line.get(x))
+                                   .cloned()
+                                   .unwrap_or(Cell::new());
+                let pos = Pos::new(x, y);
+                if self.is_match(&pos) {
+                    highlight(&mut cell);
+                }
+                if pos == self.cursor || self.is_selected(&pos) {
+                    cell.reverse = !cell.reverse;
+                }
+                cell.dirty = true;
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+
+    /// The selected text, with trailing blanks trimmed from each line
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => return None,
+        };
+
+        let mut lines: Vec<String> = vec![];
+        for y in start.y..end.y + 1 {
+            let first = if y == start.y { start.x } else { 0 };
+            let last = if y == end.y { end.x } else { self.size.width - 1 };
+
+            let mut bytes: Vec<u8> = vec![];
+            for x in first..last + 1 {
+                match self.lines[y].get(x) {
+                    Some(cell) if cell.is_continuation() => {}
+                    Some(cell) if !cell.chars.is_empty() => bytes.extend(cell.chars.iter()),
+                    _ => bytes.push(b' '),
+                }
+            }
+            lines.push(String::from_utf8_lossy(&bytes).trim_right().to_string());
+        }
+
+        Some(lines.join("\n"))
+    }
+
+    /// Where the cursor is, counting every line the server has rather than just those fetched
+    pub fn position_text(&self) -> String {
+        let unfetched = self.scrollback_total - cmp::min(self.scrollback_fetched,
+                                                         self.scrollback_total);
+        format!("{}/{}",
+                unfetched + self.cursor.y + 1,
+                unfetched + self.lines.len())
+    }
+
+    /// Start looking for a pattern, which is typed with search_input
+    pub fn search_start(&mut self, is_forward: bool) {
+        let is_regex = self.search.as_ref().map_or(false, |s| s.is_regex);
+        self.search = Some(Search {
+            pattern: String::new(),
+            is_regex: is_regex,
+            is_forward: is_forward,
+            origin: self.cursor.clone(),
+            matches: vec![],
+            regex: None,
+            is_valid: true,
+        });
+    }
+
+    /// Edits the pattern and jumps to the first match from where the search started. Backspace
+    /// removes a char and ctrl-r switches between plain text and regex.
+    pub fn search_input(&mut self, bytes: &[u8]) {
+        {
+            let search = match self.search.as_mut() {
+                Some(search) => search,
+                None => return,
+            };
+
+            for ch in String::from_utf8_lossy(bytes).chars() {
+                match ch {
+                    '\x7f' | '\x08' => {
+                        search.pattern.pop();
+                    }
+                    '\x12' => search.is_regex = !search.is_regex,
+                    ch if ch.is_control() => {}
+                    ch => search.pattern.push(ch),
+                }
+            }
+        }
+
+        self.find_matches();
+        let (origin, is_forward) = match self.search.as_ref() {
+            Some(search) => (search.origin.clone(), search.is_forward),
+            None => return,
+        };
+        if !self.jump_to_match(&origin, is_forward, true) {
+            self.move_to(origin.x as isize, origin.y as isize);
+        }
+    }
+
+    /// Puts the cursor back where it was and stops highlighting matches
+    pub fn search_cancel(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.move_to(search.origin.x as isize, search.origin.y as isize);
+        }
+    }
+
+    /// Keeps the matches for n and N, unless nothing was typed
+    pub fn search_confirm(&mut self) {
+        if self.search.as_ref().map_or(false, |s| s.pattern.is_empty()) {
+            self.search = None;
+        }
+    }
+
+    /// What the status line shows while a pattern is typed, like `regex ?a.b  3 matches`
+    pub fn search_text(&self) -> Option<String> {
+        self.search.as_ref().map(|search| {
+            let kind = if search.is_regex { "regex" } else { "search" };
+            let direction = if search.is_forward { '/' } else { '?' };
+            let found = if !search.is_valid {
+                "invalid".to_string()
+            } else {
+                format!("{} matches", search.matches.len())
+            };
+            format!("{} {}{}  {}", kind, direction, search.pattern, found)
+        })
+    }
+
+    fn search_again(&mut self, is_same_direction: bool) {
+        let is_forward = match self.search.as_ref() {
+            Some(search) => search.is_forward == is_same_direction,
+            None => return,
+        };
+        let cursor = self.cursor.clone();
+        self.jump_to_match(&cursor, is_forward, false);
+    }
+
+    /// Looks for the pattern in every line held. Matches don't span lines.
+    fn find_matches(&mut self) {
+        let search = match self.search.as_mut() {
+            Some(search) => search,
+            None => return,
+        };
+        search.matches.clear();
+        search.regex = None;
+        search.is_valid = true;
+        if search.pattern.is_empty() {
+            return;
+        }
+
+        let pattern = if search.is_regex {
+            search.pattern.clone()
+        } else {
+            regex::quote(&search.pattern)
+        };
+        let regex = match Regex::new(&pattern) {
+            Ok(regex) => regex,
+            Err(_) => {
+                search.is_valid = false;
+                return;
+            }
+        };
+
+        search.matches = find_in_lines(&regex, &self.lines);
+        search.regex = Some(regex);
+    }
+
+    /// Moves to the next match after the position, or the one before it going backwards, wrapping
+    /// around at the ends. Returns false if there are no matches.
+    fn jump_to_match(&mut self, from: &Pos, is_forward: bool, is_inclusive: bool) -> bool {
+        let found = {
+            let matches = match self.search.as_ref() {
+                Some(search) => &search.matches,
+                None => return false,
+            };
+            let from = (from.y, from.x);
+            let is_after = |m: &&(usize, usize, usize)| {
+                (m.0, m.1) > from || (is_inclusive && (m.0, m.1) == from)
+            };
+            let is_before = |m: &&(usize, usize, usize)| {
+                (m.0, m.1) < from || (is_inclusive && (m.0, m.1) == from)
+            };
+
+            if is_forward {
+                matches.iter().find(is_after).or(matches.first()).cloned()
+            } else {
+                matches.iter().rev().find(is_before).or(matches.last()).cloned()
+            }
+        };
+
+        match found {
+            Some((y, x, _)) => {
+                self.move_to(x as isize, y as isize);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_match(&self, pos: &Pos) -> bool {
+        match self.search.as_ref() {
+            Some(search) => {
+                search.matches
+                      .iter()
+                      .any(|&(y, start, end)| y == pos.y && start <= pos.x && pos.x < end)
+            }
+            None => false,
+        }
+    }
+
+    /// The start and end of the selection, in the order they appear
+    fn selection(&self) -> Option<(Pos, Pos)> {
+        self.mark.as_ref().map(|mark| {
+            if (mark.y, mark.x) <= (self.cursor.y, self.cursor.x) {
+                (mark.clone(), self.cursor.clone())
+            } else {
+                (self.cursor.clone(), mark.clone())
+            }
+        })
+    }
+
+    fn is_selected(&self, pos: &Pos) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                (start.y, start.x) <= (pos.y, pos.x) && (pos.y, pos.x) <= (end.y, end.x)
+            }
+            None => false,
+        }
+    }
+
+    fn last_line(&self) -> usize {
+        cmp::max(self.lines.len(), 1) - 1
+    }
+
+    fn line_len(&self, y: usize) -> usize {
+        self.lines.get(y).map(|line| line.len()).unwrap_or(0)
+    }
+
+    fn is_blank(&self, pos: &Pos) -> bool {
+        match self.lines.get(pos.y).and_then(|line| line.get(pos.x)) {
+            Some(cell) => {
+                !cell.is_continuation() && (cell.chars.is_empty() || cell.chars == b" ")
+            }
+            None => true,
+        }
+    }
+
+    /// Moves to the next position, wrapping onto the next line. Returns false at the end.
+    fn step_forward(&mut self) -> bool {
+        if self.cursor.x + 1 < self.size.width {
+            self.cursor.x += 1;
+        } else if self.cursor.y < self.last_line() {
+            self.cursor = Pos::new(0, self.cursor.y + 1);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn step_back(&mut self) -> bool {
+        if self.cursor.x > 0 {
+            self.cursor.x -= 1;
+        } else if self.cursor.y > 0 {
+            self.cursor = Pos::new(self.size.width - 1, self.cursor.y - 1);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// To the start of the next word, like vi's w
+    fn word_forward(&mut self) {
+        while !self.is_blank(&self.cursor.clone()) && self.step_forward() {}
+        while self.is_blank(&self.cursor.clone()) && self.step_forward() {}
+        let (x, y) = (self.cursor.x as isize, self.cursor.y as isize);
+        self.move_to(x, y);
+    }
+
+    /// To the start of this word, or the one before if already there, like vi's b
+    fn word_back(&mut self) {
+        self.step_back();
+        while self.is_blank(&self.cursor.clone()) && self.step_back() {}
+        loop {
+            let mut before = self.cursor.clone();
+            if before.x == 0 {
+                break;
+            }
+            before.x -= 1;
+            if self.is_blank(&before) {
+                break;
+            }
+            self.cursor = before;
+        }
+        let (x, y) = (self.cursor.x as isize, self.cursor.y as isize);
+        self.move_to(x, y);
+    }
+
+    /// Clamps the position to what exists and scrolls it into view
+    fn move_to(&mut self, x: isize, y: isize) {
+        let max_x = cmp::max(self.size.width, 1) as isize - 1;
+        let max_y = self.last_line() as isize;
+        let x = cmp::min(cmp::max(x, 0), max_x) as usize;
+        let y = cmp::min(cmp::max(y, 0), max_y) as usize;
+        self.cursor = Pos::new(x, y);
+
+        let height = cmp::max(self.size.height, 1);
+        if y < self.top {
+            self.top = y;
+        } else if y >= self.top + height {
+            self.top = y + 1 - height;
+        }
+    }
+}
+
+/// The line, first column and the column after the last of each match in the lines, with lines
+/// counted from the first one given
+fn find_in_lines(regex: &Regex, lines: &[Vec<Cell>]) -> Vec<(usize, usize, usize)> {
+    let mut matches = vec![];
+    for (y, line) in lines.iter().enumerate() {
+        // Which column each byte of the text came from. Cells are decoded one at a time, so an
+        // invalid byte turning into a longer replacement char still maps back to its own cell.
+        let mut text = String::new();
+        let mut columns: Vec<usize> = vec![];
+        for (x, cell) in line.iter().enumerate().filter(|&(_, c)| !c.is_continuation()) {
+            let chars: &[u8] = if cell.chars.is_empty() { b" " } else { &cell.chars };
+            let decoded = String::from_utf8_lossy(chars);
+            columns.extend((0..decoded.len()).map(|_| x));
+            text.push_str(&decoded);
+        }
+
+        for (start, end) in regex.find_iter(&text) {
+            if start < end && end <= columns.len() {
+                matches.push((y, columns[start], columns[end - 1] + 1));
+            }
+        }
+    }
+    matches
+}
+
+/// Black on yellow, so matches stand out from the selection
+fn highlight(cell: &mut Cell) {
+    cell.fg_palette = 0;
+    cell.fg_rgb = palette_rgb(0);
+    cell.bg_palette = 3;
+    cell.bg_rgb = palette_rgb(3);
+}
+
+fn cells_from_vterm(line: &[ScreenCell]) -> Vec<Cell> {
+    let mut cells: Vec<Cell> = line.iter()
+                                   .map(|vterm_cell| {
+                                       let mut cell = Cell::new();
+                                       cell.update_from_vterm_cell(vterm_cell);
+                                       cell
+                                   })
+                                   .collect();
+    Cell::mark_continuations(&mut cells);
+    cells
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use cell_buffer::Cell;
+    use vterm_sys::{Pos, ScreenCell, Size};
+
+    fn lines(text: &[&str]) -> Vec<Vec<Cell>> {
+        text.iter()
+            .map(|line| {
+                line.bytes()
+                    .map(|byte| {
+                        let mut cell = Cell::new();
+                        cell.chars = vec![byte];
+                        cell
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn type_keys(copy_mode: &mut CopyMode, keys: &str) {
+        for key in keys.bytes() {
+            copy_mode.handle_key(key);
+        }
+    }
+
+    #[test]
+    fn it_starts_on_the_bottom_line() {
+        let mut copy_mode = CopyMode::new("a", lines(&["one  ", "two  "]), Size::new(5, 2));
+        type_keys(&mut copy_mode, "v$");
+
+        assert_eq!(copy_mode.selected_text(), Some("two".to_string()));
+    }
+
+    #[test]
+    fn it_copies_wide_chars_once() {
+        let mut line = lines(&["  x"]).remove(0);
+        line[0].chars = "中".to_string().into_bytes();
+        line[0].width = 2;
+        line[1].chars.clear();
+        line[1].width = 0;
+        let mut copy_mode = CopyMode::new("a", vec![line], Size::new(3, 1));
+        type_keys(&mut copy_mode, "v$");
+
+        assert_eq!(copy_mode.selected_text(), Some("中x".to_string()));
+    }
+
+    #[test]
+    fn it_selects_across_lines() {
+        let mut copy_mode = CopyMode::new("a",
+                                          lines(&["one two", "three  ", "four   "]),
+                                          Size::new(7, 3));
+        type_keys(&mut copy_mode, "kkwvjj");
+
+        assert_eq!(copy_mode.selected_text(),
+                   Some("two\nthree\nfour".to_string()));
+    }
+
+    #[test]
+    fn it_has_nothing_to_yank_without_a_selection() {
+        let copy_mode = CopyMode::new("a", lines(&["one"]), Size::new(3, 1));
+        assert_eq!(copy_mode.selected_text(), None);
+    }
+
+    #[test]
+    fn older_lines_go_above_and_leave_the_cursor_where_it_was() {
+        let mut copy_mode = CopyMode::new("a", lines(&["screen"]), Size::new(6, 1));
+        let mut old_cell: ScreenCell = Default::default();
+        old_cell.chars = b"x".to_vec();
+        copy_mode.add_scrollback(vec![vec![old_cell]], 5);
+
+        assert_eq!(copy_mode.position_text(), "6/6");
+        type_keys(&mut copy_mode, "vk");
+        assert_eq!(copy_mode.selected_text(), Some("x\ns".to_string()));
+        assert_eq!(copy_mode.wants_scrollback(), Some(1));
+    }
+
+    #[test]
+    fn searching_jumps_to_matches_as_the_pattern_is_typed() {
+        let mut copy_mode = CopyMode::new("a",
+                                          lines(&["ab ab", "abc  ", "xyz  "]),
+                                          Size::new(5, 3));
+        copy_mode.search_start(false);
+        copy_mode.search_input(b"ab");
+        assert_eq!(copy_mode.cursor, Pos::new(0, 1));
+
+        copy_mode.search_input(b"c");
+        assert_eq!(copy_mode.cursor, Pos::new(0, 1));
+        assert_eq!(copy_mode.search_text(), Some("search ?abc  1 matches".to_string()));
+    }
+
+    #[test]
+    fn n_and_shift_n_go_between_matches_and_wrap_around() {
+        let mut copy_mode = CopyMode::new("a",
+                                          lines(&["ab ab", "abc  ", "xyz  "]),
+                                          Size::new(5, 3));
+        copy_mode.search_start(true);
+        copy_mode.search_input(b"ab");
+        copy_mode.search_confirm();
+        assert_eq!(copy_mode.cursor, Pos::new(0, 0));
+
+        type_keys(&mut copy_mode, "n");
+        assert_eq!(copy_mode.cursor, Pos::new(3, 0));
+        type_keys(&mut copy_mode, "nn");
+        assert_eq!(copy_mode.cursor, Pos::new(0, 0));
+        type_keys(&mut copy_mode, "N");
+        assert_eq!(copy_mode.cursor, Pos::new(0, 1));
+    }
+
+    #[test]
+    fn searching_with_a_regex() {
+        let mut copy_mode = CopyMode::new("a", lines(&["a1 b22", "      "]), Size::new(6, 2));
+        copy_mode.search_start(false);
+        // ctrl-r
+        copy_mode.search_input(b"\x12");
+        copy_mode.search_input(b"[0-9]+");
+
+        assert_eq!(copy_mode.cursor, Pos::new(4, 0));
+        let highlighted: Vec<bool> = copy_mode.render()[..6]
+                                              .iter()
+                                              .map(|c| c.bg_palette == 3)
+                                              .collect();
+        assert_eq!(highlighted, vec![false, true, false, false, true, true]);
+    }
+
+    #[test]
+    fn matches_after_invalid_bytes_land_on_their_own_cells() {
+        let mut line = lines(&[" abc"]).remove(0);
+        line[0].chars = vec![0xff];
+        let mut copy_mode = CopyMode::new("a", vec![line], Size::new(4, 1));
+        copy_mode.search_start(false);
+        copy_mode.search_input(b"bc");
+
+        assert_eq!(copy_mode.cursor, Pos::new(2, 0));
+    }
+
+    #[test]
+    fn fetched_lines_are_searched_too() {
+        let mut copy_mode = CopyMode::new("a", lines(&["ab"]), Size::new(2, 1));
+        copy_mode.search_start(false);
+        copy_mode.search_input(b"b");
+
+        let mut old_cell: ScreenCell = Default::default();
+        old_cell.chars = b"b".to_vec();
+        copy_mode.add_scrollback(vec![vec![old_cell]], 1);
+
+        assert_eq!(copy_mode.search_text(), Some("search ?b  2 matches".to_string()));
+        type_keys(&mut copy_mode, "n");
+        assert_eq!(copy_mode.cursor, Pos::new(0, 0));
+    }
+
+    #[test]
+    fn cancelling_a_search_goes_back() {
+        let mut copy_mode = CopyMode::new("a", lines(&["ab", "  "]), Size::new(2, 2));
+        copy_mode.search_start(false);
+        copy_mode.search_input(b"b");
+        assert_eq!(copy_mode.cursor, Pos::new(1, 0));
+
+        copy_mode.search_cancel();
+        assert_eq!(copy_mode.cursor, Pos::new(0, 1));
+        assert_eq!(copy_mode.search_text(), None);
+    }
+
+    #[test]
+    fn the_cursor_and_selection_are_in_reverse_video() {
+        let mut copy_mode = CopyMode::new("a", lines(&["abc"]), Size::new(3, 1));
+        type_keys(&mut copy_mode, "vl");
+
+        let reversed: Vec<bool> = copy_mode.render().iter().map(|c| c.reverse).collect();
+        assert_eq!(reversed, vec![true, true, false]);
+    }
+}
@@ -29,6 +29,25 @@ impl Layout {
         &mut self.tree
     }
 
+    /// Remove the wraps with the given name, along with their children. The tree is rebuilt
+    /// without them rather than detaching, since ego_tree still iterates over detached nodes.
+    pub fn remove_wrap(&mut self, name: &str) {
+        let mut tree = ego_tree::Tree::new(self.tree.root().value().clone());
+        let root_id = tree.root().id();
+        Layout::copy_children(self.tree.root(), &mut tree, root_id, name);
+        self.tree = tree;
+    }
+
+    fn copy_children(from: ego_tree::NodeRef<Wrap>,
+                     to: &mut ego_tree::Tree<Wrap>,
+                     parent_id: ego_tree::NodeId<Wrap>,
+                     skip_name: &str) {
+        for child in from.children().filter(|c| &c.value().name()[..] != skip_name) {
+            let child_id = to.get_mut(parent_id).append(child.value().clone()).id();
+            Layout::copy_children(child, to, child_id, skip_name);
+        }
+    }
+
     /// recalculate to account for changes
     pub fn flush_changes(&mut self) {
         self.update_root_wrap();
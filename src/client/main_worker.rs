@@ -1,9 +1,13 @@
+use libc;
+use std::cmp;
+use std::env;
 use std::io::prelude::*;
 use super::paint::*;
 use std::ops::IndexMut;
 use std::sync::mpsc::*;
 use std::thread::{self, JoinHandle};
 use super::*;
+use super::copy_mode::*;
 use super::servers::*;
 use uuid::Uuid;
 use vterm_sys::{self, Pos, Size, Rect, RectAssist};
@@ -23,21 +27,40 @@ pub struct MainWorker<F: 'static + Write + Send> {
     pub servers: Servers,
     pub modal_key_handler: modal::ModalKeyHandler,
     pub tty_ioctl_config: TtyIoCtlConfig,
+    pub config: ClientConfig,
     pub layout: layout::Layout,
-    selected_program_id: Option<String>,
+    /// the wrap of the program that user input goes to
+    selected_wrap_name: Option<String>,
+    /// the server new programs are started on
+    selected_server_id: Option<String>,
+    /// the command line being typed at the prompt
+    prompt: String,
+    /// set while the user is moving around a program's scrollback
+    copy_mode: Option<CopyMode>,
+    mouse_parser: mouse::MouseParser,
+    /// the wrap a mouse button went down in, which gets the mouse until the button is let go
+    mouse_wrap_name: Option<String>,
+    /// what the outer terminal's title was last set to
+    outer_title: Option<String>,
+    /// what the outer terminal's cursor style was last set to, 0 being the user's default
+    outer_cursor_style: u8,
     painter: TtyPainter<F>,
     screen: CellBuffer,
 }
 
 static STATUS_LINE: &'static str = "status_line";
 
+/// How much scrollback copy mode asks for at a time
+const SCROLLBACK_FETCH_LINES: usize = 1000;
+
 impl<F: 'static + Write + Send> MainWorker<F> {
     pub fn spawn(tty_ioctl_config: TtyIoCtlConfig,
+                 config: ClientConfig,
                  io: F)
                  -> (Sender<ClientMsg>, JoinHandle<()>) {
         let (tx, rx) = channel::<ClientMsg>();
         let layout = layout::Layout::new(Size::new(tty_ioctl_config.cols, tty_ioctl_config.rows));
-        let mut worker = MainWorker::new(rx, tx.clone(), tty_ioctl_config, layout, io);
+        let mut worker = MainWorker::new(rx, tx.clone(), tty_ioctl_config, config, layout, io);
 
         info!("spawning main worker");
         let handle = thread::spawn(move || {
@@ -51,6 +74,7 @@ impl<F: 'static + Write + Send> MainWorker<F> {
     fn new(rx: Receiver<ClientMsg>,
            tx: Sender<ClientMsg>,
            tty_ioctl_config: TtyIoCtlConfig,
+           config: ClientConfig,
            layout: layout::Layout,
            io: F)
            -> MainWorker<F> {
@@ -65,8 +89,16 @@ impl<F: 'static + Write + Send> MainWorker<F> {
             servers: Default::default(),
             modal_key_handler: modal::ModalKeyHandler::new_with_graph(),
             tty_ioctl_config: tty_ioctl_config.clone(),
+            config: config,
             layout: layout,
-            selected_program_id: None,
+            selected_wrap_name: None,
+            selected_server_id: None,
+            prompt: String::new(),
+            copy_mode: None,
+            mouse_parser: mouse::MouseParser::new(),
+            mouse_wrap_name: None,
+            outer_title: None,
+            outer_cursor_style: 0,
             painter: TtyPainter::new(io, size.clone()),
             screen: CellBuffer::new(size),
         };
@@ -83,6 +115,13 @@ impl<F: 'static + Write + Send> MainWorker<F> {
         self.layout.tree_mut().root_mut().append(status_line);
         self.layout.flush_changes();
 
+        if self.config.mouse {
+            self.painter.set_mouse_reporting(true);
+        }
+        if self.config.set_title {
+            self.painter.save_title();
+        }
+
         self.tx.send(ClientMsg::Clear).unwrap();
         self.tx.send(ClientMsg::LayoutDamage).unwrap();
         self.tx.send(ClientMsg::StatusLineDamage).unwrap();
@@ -101,32 +140,111 @@ impl<F: 'static + Write + Send> MainWorker<F> {
                     self.quit();
                     break;
                 }
-                ClientMsg::ServerAdd { server } => self.servers.add_server(server),
-                ClientMsg::ProgramAdd { server_id, program_id } => {
-                    self.add_program(server_id, program_id)
+                ClientMsg::ServerAdd { server } => self.add_server(server),
+                ClientMsg::ServerUpdate { server } => self.update_server(server),
+                ClientMsg::ServerRemove { server_id } => self.remove_server(server_id),
+                ClientMsg::ProgramAdd { server_id, program_id, size } => {
+                    self.add_program(server_id, program_id, size)
+                }
+                ClientMsg::ProgramResize { server_id, program_id, size } => {
+                    self.program_resize(server_id, program_id, size)
+                }
+                ClientMsg::ProgramExited { server_id, program_id, status } => {
+                    self.program_exited(server_id, program_id, status)
+                }
+                ClientMsg::ProgramUpdate { server_id, program_id } => {
+                    self.program_update(server_id, program_id)
+                }
+                ClientMsg::ProgramRemove { server_id, program_id } => {
+                    self.remove_program(server_id, program_id)
+                }
+                ClientMsg::ProgramScrollback { server_id, program_id, lines, total, .. } => {
+                    self.program_scrollback(server_id, program_id, lines, total)
+                }
+                ClientMsg::Clipboard { bytes, .. } => self.set_clipboard(bytes),
+                ClientMsg::ProgramBell { server_id, program_id } => {
+                    self.program_bell(server_id, program_id)
+                }
+                ClientMsg::ProgramActivity { server_id, program_id } => {
+                    self.mark_program(server_id, program_id, |p| p.has_activity = true)
+                }
+                ClientMsg::ProgramSilence { server_id, program_id } => {
+                    // a long build finishing is usually what makes a program go quiet
+                    self.mark_program(server_id, program_id, |p| {
+                        p.has_activity = false;
+                        p.has_silence = true;
+                    })
+                }
+                ClientMsg::ProgramCursorStyle { server_id, program_id, style } => {
+                    self.program_cursor_style(server_id, program_id, style)
+                }
+                ClientMsg::ProgramMonitorSilence { server_id, program_id, is_on } => {
+                    self.program_monitor_silence(server_id, program_id, is_on)
                 }
-                ClientMsg::ProgramDamage { program_id, cells, rect } => {
-                    self.program_damage(program_id, cells, rect)
+                ClientMsg::ProgramTitle { server_id, program_id, title, icon_name } => {
+                    self.program_title(server_id, program_id, title, icon_name)
+                }
+                ClientMsg::ProgramDamage { server_id, program_id, cells, rect } => {
+                    self.program_damage(server_id, program_id, cells, rect)
                 }
                 ClientMsg::Clear { .. } => self.clear(),
                 ClientMsg::LayoutDamage { .. } => self.layout_damage(),
-                ClientMsg::ProgramMoveCursor { program_id, old: _, new, is_visible } => {
-                    self.move_cursor(program_id, new, is_visible)
+                ClientMsg::ProgramMoveCursor { server_id, program_id, old: _, new, is_visible } => {
+                    self.move_cursor(server_id, program_id, new, is_visible)
                 }
                 ClientMsg::LayoutSwap { layout } => self.layout = layout,
                 ClientMsg::StatusLineDamage => self.damage_status_line(),
+                ClientMsg::TtyResize { size } => self.tty_resize(size),
                 ClientMsg::UserInput { bytes } => {
+                    let (bytes, mouse_events) = self.mouse_parser.parse(&bytes);
+                    for event in mouse_events {
+                        self.mouse_event(event);
+                    }
+                    if bytes.is_empty() {
+                        continue;
+                    }
+
                     self.modal_key_handler.write(&bytes).unwrap();
-                    while let Some(user_action) = self.modal_key_handler.actions_queue.pop() {
+                    // in the order they were typed, or pasted text would come out backwards
+                    let user_actions: Vec<_> =
+                        self.modal_key_handler.actions_queue.drain(..).collect();
+                    for user_action in user_actions {
                         match user_action {
+                            modal::UserAction::CopyModeExit => self.copy_mode_exit(),
+                            modal::UserAction::CopyModeInput { bytes } => {
+                                self.copy_mode_input(bytes)
+                            }
+                            modal::UserAction::CopyModeStart => self.copy_mode_start(),
+                            modal::UserAction::CopyModeYank => self.copy_mode_yank(),
+                            modal::UserAction::Paste => self.paste(),
+                            modal::UserAction::Detach => {
+                                self.detach();
+                                break 'outer;
+                            }
                             modal::UserAction::ModeChange { name } => self.mode_change(&name),
                             modal::UserAction::ProgramFocus => self.program_focus_cmd(),
                             modal::UserAction::ProgramInput { bytes: fites } => {
                                 self.program_input_cmd(fites)
                             }
                             modal::UserAction::ProgramStart => self.program_start_cmd(),
+                            modal::UserAction::ProgramDismiss => self.program_dismiss_cmd(),
+                            modal::UserAction::ProgramKill => self.program_kill_cmd(),
+                            modal::UserAction::ProgramMonitorSilence => {
+                                self.program_monitor_silence_cmd()
+                            }
+                            modal::UserAction::ProgramRestart => self.program_restart_cmd(),
                             modal::UserAction::ProgramSelectPrev => self.program_select_prev(),
                             modal::UserAction::ProgramSelectNext => self.program_select_next(),
+                            modal::UserAction::PromptCancel => self.prompt_cancel(),
+                            modal::UserAction::PromptInput { bytes } => self.prompt_input(bytes),
+                            modal::UserAction::PromptStart => self.prompt_start(),
+                            modal::UserAction::PromptSubmit => self.prompt_submit(),
+                            modal::UserAction::SearchBackward => self.search_start(false),
+                            modal::UserAction::SearchCancel => self.search_cancel(),
+                            modal::UserAction::SearchConfirm => self.search_confirm(),
+                            modal::UserAction::SearchForward => self.search_start(true),
+                            modal::UserAction::SearchInput { bytes } => self.search_input(bytes),
+                            modal::UserAction::ServerSelectNext => self.server_select_next(),
                             modal::UserAction::Quit => {
                                 self.quit();
                                 break 'outer;
@@ -142,56 +260,603 @@ impl<F: 'static + Write + Send> MainWorker<F> {
                 _ => warn!("unhandled msg {:?}", msg),
             }
         }
+
+        if self.config.mouse {
+            self.painter.set_mouse_reporting(false);
+        }
+        if self.config.set_title {
+            self.painter.restore_title();
+        }
+        if self.outer_cursor_style != 0 {
+            self.painter.set_cursor_style(0);
+        }
     }
 
     fn quit(&self) {
         info!("quit!");
-        for server in self.servers.iter() {
-            server.tx.send(::server::ServerMsg::Quit).unwrap();
+        for server in self.servers.iter().filter(|s| s.is_connected) {
+            if let Err(e) = server.tx.send(::server::ServerMsg::Quit) {
+                warn!("couldnt tell server {:?} to quit: {}", server.id, e);
+            }
+        }
+    }
+
+    fn add_server(&mut self, server: Server) {
+        info!("adding server {:?}", server.id);
+        if self.selected_server_id.is_none() {
+            self.selected_server_id = Some(server.id.clone());
+        }
+        self.servers.add_server(server);
+        self.damage_status_line();
+    }
+
+    /// The server has been reconnected to, so its panes can be shown again
+    fn update_server(&mut self, server: Server) {
+        info!("updating server {:?}", server.id);
+        if self.selected_server_id.is_none() {
+            self.selected_server_id = Some(server.id.clone());
+        }
+        self.servers.update_server(server);
+        self.update_subscriptions();
+        self.damage_status_line();
+    }
+
+    /// The connection to the server is gone. Its panes stay where they are, marked disconnected,
+    /// in case it comes back.
+    fn remove_server(&mut self, server_id: String) {
+        warn!("lost server {:?}", server_id);
+        self.servers.disconnect_server(&server_id);
+
+        if self.selected_server_id.as_ref() == Some(&server_id) {
+            self.selected_server_id = self.servers
+                                          .iter()
+                                          .find(|s| s.is_connected)
+                                          .map(|s| s.id.clone());
+        }
+
+        self.draw_disconnected(&server_id);
+        self.damage_status_line();
+    }
+
+    /// Write a notice over the top line of each of the server's panes
+    fn draw_disconnected(&mut self, server_id: &str) {
+        let wrap_names: Vec<String> = match self.servers.find(server_id) {
+            Some(server) => {
+                server.programs.iter().map(|p| wrap_name_for(server_id, &p.id)).collect()
+            }
+            None => return,
+        };
+
+        let screen_rect = Rect::new(Pos::new(0, 0), self.layout.size.clone());
+        for wrap in self.layout.tree().values().filter(|w| wrap_names.contains(w.name())) {
+            let (x, y) = match (wrap.computed_x(), wrap.computed_y()) {
+                (Some(x), Some(y)) => (x, y),
+                _ => continue,
+            };
+
+            let notice = " disconnected ";
+            let positions = (x..x + wrap.computed_width().unwrap()).map(|x| Pos::new(x, y));
+            for (pos, ch) in positions.filter(|p| screen_rect.contains(p)).zip(notice.chars()) {
+                let cell = self.screen.index_mut(pos);
+                cell.clear();
+                cell.chars = ch.to_string().into_bytes();
+                cell.reverse = true;
+            }
+        }
+
+        self.painter.draw_screen(&mut self.screen);
+    }
+
+    /// Change which server new programs are started on
+    fn server_select_next(&mut self) {
+        let ids: Vec<String> = self.servers
+                                   .iter()
+                                   .filter(|s| s.is_connected)
+                                   .map(|s| s.id.clone())
+                                   .collect();
+        if ids.is_empty() {
+            return;
         }
+
+        let next = match self.selected_server_id
+                             .as_ref()
+                             .and_then(|id| ids.iter().position(|i| i == id)) {
+            Some(i) => (i + 1) % ids.len(),
+            None => 0,
+        };
+        self.selected_server_id = Some(ids[next].clone());
+        self.damage_status_line();
+    }
+
+    /// Leave the servers and their programs running, so another client can attach to them later.
+    /// Closing the connection is enough for a server to forget about this client.
+    fn detach(&self) {
+        info!("detach!");
     }
 
     fn program_input_cmd(&self, bytes: Vec<u8>) {
-        if let Some(program_id) = self.selected_program_id.clone() {
-            if let Some(server) = self.servers
-                                      .iter()
-                                      .find(|s| s.programs.iter().any(|p| p.id == program_id)) {
-                trace!("sending input to program {} {:?}", program_id, bytes);
-                server.tx
-                      .send(::server::ServerMsg::ProgramInput {
-                          program_id: program_id,
-                          bytes: bytes,
-                      })
-                      .unwrap();
+        if let Some(wrap_name) = self.selected_wrap_name.clone() {
+            if let Some((server, program)) = self.servers.find_by_wrap_name(&wrap_name) {
+                if !server.is_connected {
+                    trace!("dropping input for program {} on disconnected server", program.id);
+                    return;
+                }
+
+                trace!("sending input to program {} {:?}", program.id, bytes);
+                let result = server.tx.send(::server::ServerMsg::ProgramInput {
+                    program_id: program.id.clone(),
+                    bytes: bytes,
+                });
+                if let Err(e) = result {
+                    warn!("couldnt send input to server {:?}: {}", server.id, e);
+                }
             } else {
-                warn!("server doesn't have a program called {:?}", program_id);
+                warn!("no server has a program for wrap {:?}", wrap_name);
             }
         } else {
             warn!("program input without selected program");
         }
     }
 
+    /// Presses focus the pane they are on. Events over a program's screen are sent to it, and its
+    /// server passes them on if the program turned on mouse reporting. Once a button is down,
+    /// the pane it went down in gets everything until it is let go, so a drag that leaves the pane
+    /// still ends.
+    fn mouse_event(&mut self, event: mouse::MouseEvent) {
+        let held_wrap_name = self.mouse_wrap_name.clone();
+        if event.is_release {
+            self.mouse_wrap_name = None;
+        }
+
+        let wrap_name = match held_wrap_name.clone().or_else(|| self.wrap_name_at(&event.pos)) {
+            Some(wrap_name) => wrap_name,
+            None => return,
+        };
+
+        if event.is_press() && self.selected_wrap_name.as_ref() != Some(&wrap_name) {
+            self.selected_wrap_name = Some(wrap_name.clone());
+            self.add_border_to_selected_wrap_name_wrap();
+        }
+
+        let rect = match self.layout.tree().values().find(|w| *w.name() == wrap_name) {
+            Some(wrap) => {
+                Rect::new(Pos::new(wrap.computed_x().unwrap(), wrap.computed_y().unwrap()),
+                          Size::new(wrap.computed_width().unwrap(),
+                                    wrap.computed_height().unwrap()))
+            }
+            None => return,
+        };
+        // the border and margin aren't the program's
+        if held_wrap_name.is_none() && !rect.contains(&event.pos) {
+            return;
+        }
+        if self.copy_mode.as_ref().map_or(false, |c| c.wrap_name == wrap_name) {
+            return;
+        }
+        if event.is_press() {
+            self.mouse_wrap_name = Some(wrap_name.clone());
+        }
+
+        // a drag out of the pane is held at its edge
+        let pos = Pos::new(cmp::min(event.pos.x.saturating_sub(rect.origin.x),
+                                    rect.size.width.saturating_sub(1)),
+                           cmp::min(event.pos.y.saturating_sub(rect.origin.y),
+                                    rect.size.height.saturating_sub(1)));
+
+        if let Some((server, program)) = self.servers.find_by_wrap_name(&wrap_name) {
+            if !server.is_connected {
+                return;
+            }
+
+            trace!("sending mouse event to program {} {:?}", program.id, event);
+            let result = server.tx.send(::server::ServerMsg::ProgramMouse {
+                program_id: program.id.clone(),
+                button: event.button,
+                pos: pos,
+                is_release: event.is_release,
+            });
+            if let Err(e) = result {
+                warn!("couldnt send mouse event to server {:?}: {}", server.id, e);
+            }
+        }
+    }
+
+    /// The program wrap whose box, including its border or margin, is at the position
+    fn wrap_name_at(&self, pos: &Pos) -> Option<String> {
+        let leaf_names = self.leaf_names();
+        self.layout
+            .tree()
+            .values()
+            .filter(|w| leaf_names.contains(w.name()))
+            .find(|w| {
+                match (w.outside_x(), w.outside_y(), w.outside_width(), w.outside_height()) {
+                    (Some(x), Some(y), Some(width), Some(height)) => {
+                        Rect::new(Pos::new(x, y), Size::new(width, height)).contains(pos)
+                    }
+                    _ => false,
+                }
+            })
+            .map(|w| w.name().clone())
+    }
+
+    /// Start a shell on the selected server
     fn program_start_cmd(&self) {
-        if let Some(server) = self.servers.first() {
-            trace!("starting program");
-            // for now, always start bash
-            let command_and_args: Vec<String> = vec!["bash".to_string()];
-            server.tx
-                  .send(::server::ServerMsg::ProgramStart {
-                      command_and_args: command_and_args,
-                      program_id: Uuid::new_v4().to_hyphenated_string(),
-                  })
-                  .unwrap();
+        self.start_program(None);
+    }
+
+    /// Start a program on the selected server, in the directory the client was started in. With
+    /// no command line the server runs its user's shell.
+    fn start_program(&self, command_line: Option<String>) {
+        let server = self.selected_server_id
+                         .as_ref()
+                         .and_then(|id| self.servers.find(id));
+        if let Some(server) = server.and_then(|s| if s.is_connected { Some(s) } else { None }) {
+            trace!("starting program {:?} on {:?}", command_line, server.id);
+            let cwd = env::current_dir().ok().and_then(|p| p.to_str().map(|s| s.to_string()));
+            let result = server.tx.send(::server::ServerMsg::ProgramStart {
+                program_id: Uuid::new_v4().to_hyphenated_string(),
+                command_and_args: vec![],
+                command_line: command_line,
+                cwd: cwd,
+                env: vec![],
+                size: self.new_program_size(),
+                monitor_silence: false,
+            });
+            if let Err(e) = result {
+                warn!("couldnt start program on server {:?}: {}", server.id, e);
+            }
+        } else {
+            warn!("no connected server to start a program on");
+        }
+    }
+
+    fn prompt_start(&mut self) {
+        self.prompt.clear();
+    }
+
+    /// Backspace removes the last char. Other control chars are ignored.
+    fn prompt_input(&mut self, bytes: Vec<u8>) {
+        for ch in String::from_utf8_lossy(&bytes).chars() {
+            match ch {
+                '\x7f' | '\x08' => {
+                    self.prompt.pop();
+                }
+                ch if ch.is_control() => trace!("ignoring {:?} at the prompt", ch),
+                ch => self.prompt.push(ch),
+            }
+        }
+        self.damage_status_line();
+    }
+
+    fn prompt_cancel(&mut self) {
+        self.prompt.clear();
+    }
+
+    fn prompt_submit(&mut self) {
+        let command_line = self.prompt.trim().to_string();
+        self.prompt.clear();
+        if !command_line.is_empty() {
+            self.start_program(Some(command_line));
+        }
+    }
+
+    /// Ask the selected program to terminate. The server kills it outright if it doesn't.
+    fn program_kill_cmd(&self) {
+        let wrap_name = match self.selected_wrap_name.as_ref() {
+            Some(wrap_name) => wrap_name,
+            None => return,
+        };
+
+        if let Some((server, program)) = self.servers.find_by_wrap_name(wrap_name) {
+            if program.exit_status.is_some() {
+                info!("program {:?} has already exited", program.id);
+                return;
+            }
+            if !server.is_connected {
+                return;
+            }
+
+            let msg = ::server::ServerMsg::ProgramKill {
+                program_id: program.id.clone(),
+                signal: libc::SIGTERM as u8,
+            };
+            if let Err(e) = server.tx.send(msg) {
+                warn!("couldnt send to server {:?}: {}", server.id, e);
+            }
+        }
+    }
+
+    /// Start or stop flagging the selected program when it goes quiet, like a long build
+    /// finishing. The server says when it has changed.
+    fn program_monitor_silence_cmd(&self) {
+        let wrap_name = match self.selected_wrap_name.as_ref() {
+            Some(wrap_name) => wrap_name,
+            None => return,
+        };
+
+        if let Some((server, program)) = self.servers.find_by_wrap_name(wrap_name) {
+            if !server.is_connected {
+                return;
+            }
+
+            let msg = ::server::ServerMsg::ProgramMonitorSilence {
+                program_id: program.id.clone(),
+                is_on: !program.monitor_silence,
+            };
+            if let Err(e) = server.tx.send(msg) {
+                warn!("couldnt send to server {:?}: {}", server.id, e);
+            }
+        }
+    }
+
+    /// Get rid of the selected program's pane, once it has exited
+    fn program_dismiss_cmd(&self) {
+        self.send_to_selected_exited_program(|program_id| {
+            ::server::ServerMsg::ProgramRemove { program_id: program_id }
+        });
+    }
+
+    /// Run the selected program's command again, once it has exited
+    fn program_restart_cmd(&self) {
+        self.send_to_selected_exited_program(|program_id| {
+            ::server::ServerMsg::ProgramRestart { program_id: program_id }
+        });
+    }
+
+    fn send_to_selected_exited_program<M>(&self, make_msg: M)
+        where M: Fn(String) -> ::server::ServerMsg
+    {
+        let wrap_name = match self.selected_wrap_name.as_ref() {
+            Some(wrap_name) => wrap_name,
+            None => return,
+        };
+
+        if let Some((server, program)) = self.servers.find_by_wrap_name(wrap_name) {
+            if program.exit_status.is_none() {
+                info!("program {:?} is still running", program.id);
+                return;
+            }
+            if !server.is_connected {
+                return;
+            }
+
+            if let Err(e) = server.tx.send(make_msg(program.id.clone())) {
+                warn!("couldnt send to server {:?}: {}", server.id, e);
+            }
+        }
+    }
+
+    /// Copy the selected program's screen, as it is drawn in its pane, and ask for the scrollback
+    /// above it
+    fn copy_mode_start(&mut self) {
+        let wrap_name = match self.selected_wrap_name.clone() {
+            Some(wrap_name) => wrap_name,
+            None => {
+                info!("no program selected to copy from");
+                return;
+            }
+        };
+
+        let rect = match self.layout.tree().values().find(|w| *w.name() == wrap_name) {
+            Some(wrap) => {
+                Rect::new(Pos::new(wrap.computed_x().unwrap(), wrap.computed_y().unwrap()),
+                          Size::new(wrap.computed_width().unwrap(),
+                                    wrap.computed_height().unwrap()))
+            }
+            None => return,
+        };
+
+        let screen_rect = Rect::new(Pos::new(0, 0), self.layout.size.clone());
+        let mut screen_lines: Vec<Vec<Cell>> = vec![];
+        for y in rect.min_y()..rect.max_y() {
+            let line = (rect.min_x()..rect.max_x())
+                           .map(|x| Pos::new(x, y))
+                           .filter(|pos| screen_rect.contains(pos))
+                           .map(|pos| self.screen[pos].clone())
+                           .collect();
+            screen_lines.push(line);
+        }
+
+        self.copy_mode = Some(CopyMode::new(&wrap_name, screen_lines, rect.size.clone()));
+        self.fetch_scrollback(0);
+        self.draw_copy_mode();
+    }
+
+    fn copy_mode_input(&mut self, bytes: Vec<u8>) {
+        match self.copy_mode.as_mut() {
+            Some(copy_mode) => {
+                for byte in bytes {
+                    if !copy_mode.handle_key(byte) {
+                        trace!("ignoring {:?} in copy mode", byte);
+                    }
+                }
+            }
+            None => return,
+        }
+
+        self.fetch_wanted_scrollback();
+        self.draw_copy_mode();
+        self.damage_status_line();
+    }
+
+    fn copy_mode_yank(&mut self) {
+        if let Some(text) = self.copy_mode.as_ref().and_then(|c| c.selected_text()) {
+            // every server gets a copy, so it can be pasted into any program
+            for server in self.servers.iter().filter(|s| s.is_connected) {
+                let msg = ::server::ServerMsg::BufferSet {
+                    name: ::server::DEFAULT_BUFFER.to_string(),
+                    bytes: text.clone().into_bytes(),
+                };
+                if let Err(e) = server.tx.send(msg) {
+                    warn!("couldnt set buffer on server {:?}: {}", server.id, e);
+                }
+            }
+            self.set_clipboard(text.into_bytes());
+        }
+        self.copy_mode_exit();
+    }
+
+    /// Hands the bytes to the outer terminal's clipboard, if the user wants that
+    fn set_clipboard(&mut self, bytes: Vec<u8>) {
+        if self.config.forward_clipboard {
+            self.painter.set_clipboard(&bytes);
+        }
+    }
+
+    /// The program's pane gets its real screen back
+    fn copy_mode_exit(&mut self) {
+        if self.copy_mode.take().is_some() {
+            self.repaint();
+        }
+    }
+
+    /// Searching happens in copy mode, so it is started first if need be
+    fn search_start(&mut self, is_forward: bool) {
+        if self.copy_mode.is_none() {
+            self.copy_mode_start();
+        }
+        if let Some(copy_mode) = self.copy_mode.as_mut() {
+            copy_mode.search_start(is_forward);
+        }
+        self.fetch_wanted_scrollback();
+        self.damage_status_line();
+    }
+
+    fn search_input(&mut self, bytes: Vec<u8>) {
+        if let Some(copy_mode) = self.copy_mode.as_mut() {
+            copy_mode.search_input(&bytes);
+        }
+        self.draw_copy_mode();
+        self.damage_status_line();
+    }
+
+    fn search_cancel(&mut self) {
+        if let Some(copy_mode) = self.copy_mode.as_mut() {
+            copy_mode.search_cancel();
+        }
+        self.draw_copy_mode();
+        self.damage_status_line();
+    }
+
+    fn search_confirm(&mut self) {
+        if let Some(copy_mode) = self.copy_mode.as_mut() {
+            copy_mode.search_confirm();
+        }
+        self.draw_copy_mode();
+        self.damage_status_line();
+    }
+
+    fn fetch_wanted_scrollback(&mut self) {
+        if let Some(start) = self.copy_mode.as_mut().and_then(|c| c.wants_scrollback()) {
+            self.fetch_scrollback(start);
+        }
+    }
+
+    /// Ask for scrollback lines for the program in copy mode. They come back as ProgramScrollback.
+    fn fetch_scrollback(&self, start: usize) {
+        let wrap_name = match self.copy_mode.as_ref() {
+            Some(copy_mode) => &copy_mode.wrap_name,
+            None => return,
+        };
+
+        if let Some((server, program)) = self.servers.find_by_wrap_name(wrap_name) {
+            if !server.is_connected {
+                return;
+            }
+            let result = server.tx.send(::server::ServerMsg::ProgramScrollbackFetch {
+                client_id: server.client_id.clone(),
+                program_id: program.id.clone(),
+                start: start,
+                count: SCROLLBACK_FETCH_LINES,
+            });
+            if let Err(e) = result {
+                warn!("couldnt fetch scrollback from server {:?}: {}", server.id, e);
+            }
+        }
+    }
+
+    fn program_scrollback(&mut self,
+                          server_id: String,
+                          program_id: String,
+                          lines: Vec<Vec<vterm_sys::ScreenCell>>,
+                          total: usize) {
+        let wrap_name = wrap_name_for(&server_id, &program_id);
+        match self.copy_mode.as_mut() {
+            Some(copy_mode) if copy_mode.wrap_name == wrap_name => {
+                copy_mode.add_scrollback(lines, total)
+            }
+            _ => {
+                trace!("dropping scrollback for {:?}, which isn't in copy mode", wrap_name);
+                return;
+            }
+        }
+
+        self.fetch_wanted_scrollback();
+        self.draw_copy_mode();
+        self.damage_status_line();
+    }
+
+    /// Draw the copy mode's view of the program over its pane
+    fn draw_copy_mode(&mut self) {
+        let copy_mode = match self.copy_mode.as_mut() {
+            Some(copy_mode) => copy_mode,
+            None => return,
+        };
+
+        let rect = match self.layout.tree().values().find(|w| *w.name() == copy_mode.wrap_name) {
+            Some(wrap) => {
+                Rect::new(Pos::new(wrap.computed_x().unwrap(), wrap.computed_y().unwrap()),
+                          Size::new(wrap.computed_width().unwrap(),
+                                    wrap.computed_height().unwrap()))
+            }
+            None => return,
+        };
+        if *copy_mode.size() != rect.size {
+            copy_mode.resize(rect.size.clone());
+        }
+
+        let screen_rect = Rect::new(Pos::new(0, 0), self.layout.size.clone());
+        for (cell, pos) in copy_mode.render().into_iter().zip(rect.positions()) {
+            if screen_rect.contains(&pos) {
+                *self.screen.index_mut(pos) = cell;
+            }
+        }
+
+        self.painter.draw_screen(&mut self.screen);
+        // copy mode draws its own cursor
+        self.painter.hide_cursor();
+    }
+
+    /// Type what was last yanked into the selected program. The server does the typing, since it
+    /// knows whether the program wants the paste bracketed.
+    fn paste(&self) {
+        let wrap_name = match self.selected_wrap_name.as_ref() {
+            Some(wrap_name) => wrap_name,
+            None => return,
+        };
+
+        if let Some((server, program)) = self.servers.find_by_wrap_name(wrap_name) {
+            if !server.is_connected {
+                return;
+            }
+
+            let msg = ::server::ServerMsg::BufferPaste {
+                name: ::server::DEFAULT_BUFFER.to_string(),
+                program_id: program.id.clone(),
+            };
+            if let Err(e) = server.tx.send(msg) {
+                warn!("couldnt send to server {:?}: {}", server.id, e);
+            }
         }
     }
 
     /// The main point of the command, which is to direct user keys to the program, has already
     /// been done by the modal state machine. All we have to do is make sure a program is selected.
     fn program_focus_cmd(&mut self) {
-        trace!("program_focus_cmd {:?}", self.selected_program_id);
+        trace!("program_focus_cmd {:?}", self.selected_wrap_name);
 
-        let valid_selection = if let Some(program_id) = self.selected_program_id.clone() {
-            if self.leaf_names().iter().any(|n| *n == program_id) {
+        let valid_selection = if let Some(wrap_name) = self.selected_wrap_name.clone() {
+            if self.leaf_names().iter().any(|n| *n == wrap_name) {
                 true
             } else {
                 false
@@ -206,8 +871,8 @@ impl<F: 'static + Write + Send> MainWorker<F> {
     }
 
     fn program_select_prev(&mut self) {
-        let mut selected_index = if let Some(program_id) = self.selected_program_id.clone() {
-            if let Some(i) = self.leaf_names().iter().position(|n| *n == program_id) {
+        let mut selected_index = if let Some(wrap_name) = self.selected_wrap_name.clone() {
+            if let Some(i) = self.leaf_names().iter().position(|n| *n == wrap_name) {
                 i
             } else {
                 0
@@ -220,35 +885,47 @@ impl<F: 'static + Write + Send> MainWorker<F> {
             selected_index -= 1;
         }
 
-        self.selected_program_id = Some(self.leaf_names()[selected_index].clone());
-        self.add_border_to_selected_program_id_wrap();
+        self.selected_wrap_name = Some(self.leaf_names()[selected_index].clone());
+        self.add_border_to_selected_wrap_name_wrap();
     }
 
     fn program_select_next(&mut self) {
         let leaf_names: Vec<String> = self.leaf_names();
 
-        if let Some(program_id) = self.selected_program_id.clone() {
-            if let Some(mut i) = leaf_names.iter().position(|n| *n == program_id) {
+        if let Some(wrap_name) = self.selected_wrap_name.clone() {
+            if let Some(mut i) = leaf_names.iter().position(|n| *n == wrap_name) {
                 i += 1;
                 if i < leaf_names.len() {
-                    self.selected_program_id = Some(leaf_names[i].clone());
+                    self.selected_wrap_name = Some(leaf_names[i].clone());
                 } else {
-                    self.selected_program_id = Some(leaf_names[0].clone());
+                    self.selected_wrap_name = Some(leaf_names[0].clone());
                 }
             } else {
-                self.selected_program_id = None;
+                self.selected_wrap_name = None;
             }
         }
 
-        if self.selected_program_id.is_none() && leaf_names.len() > 0 {
-            self.selected_program_id = Some(leaf_names[0].clone());
+        if self.selected_wrap_name.is_none() && leaf_names.len() > 0 {
+            self.selected_wrap_name = Some(leaf_names[0].clone());
         }
 
-        self.add_border_to_selected_program_id_wrap();
+        self.add_border_to_selected_wrap_name_wrap();
     }
 
-    fn add_border_to_selected_program_id_wrap(&mut self) {
-        if let Some(program_id) = self.selected_program_id.clone() {
+    fn add_border_to_selected_wrap_name_wrap(&mut self) {
+        if let Some(wrap_name) = self.selected_wrap_name.clone() {
+            // the user is looking at whatever it was flagged for now
+            let ids = self.servers
+                           .find_by_wrap_name(&wrap_name)
+                           .map(|(server, program)| (server.id.clone(), program.id.clone()));
+            if let Some((server_id, program_id)) = ids {
+                if let Some(program) = self.servers.find_program_mut(&server_id, &program_id) {
+                    program.has_bell = false;
+                    program.has_activity = false;
+                    program.has_silence = false;
+                }
+            }
+
             for mut wrap in self.layout.tree_mut().values_mut() {
                 if *wrap.name() == "root".to_string() {
                     continue;
@@ -256,7 +933,7 @@ impl<F: 'static + Write + Send> MainWorker<F> {
                 if *wrap.name() == STATUS_LINE {
                     continue;
                 }
-                if *wrap.name() == program_id {
+                if *wrap.name() == wrap_name {
                     wrap.set_has_border(true);
                     wrap.set_margin(0);
                 } else {
@@ -268,27 +945,336 @@ impl<F: 'static + Write + Send> MainWorker<F> {
         }
 
         self.tx.send(ClientMsg::LayoutDamage).unwrap();
+        // it shows the selected program's title
+        self.tx.send(ClientMsg::StatusLineDamage).unwrap();
+    }
+
+    /// New programs get a pane to themselves on a new line, as wide as the screen and as tall as it
+    /// is without the status line, less the pane's margin.
+    fn new_program_size(&self) -> Size {
+        let margin = 1;
+        let width = self.layout.size.width.saturating_sub(2 * margin);
+        let height = self.layout.size.height.saturating_sub(1 + 2 * margin);
+        Size::new(cmp::max(width, 1), cmp::max(height, 1))
     }
 
-    fn add_program(&mut self, server_id: String, program_id: String) {
+    fn add_program(&mut self, server_id: String, program_id: String, size: Size) {
         self.servers.add_program(&server_id,
                                  Program {
                                      id: program_id.clone(),
-                                     is_subscribed: true,
+                                     is_subscribed: false,
+                                     exit_status: None,
+                                     title: String::new(),
+                                     icon_name: String::new(),
+                                     has_bell: false,
+                                     has_activity: false,
+                                     has_silence: false,
+                                     monitor_silence: false,
+                                     cursor_style: 0,
+                                     cursor_pos: None,
+                                     is_cursor_hidden: false,
                                  });
 
         let wrap = layout::WrapBuilder::row()
-                       .name(program_id.clone())
-                       .height(24)
-                       .width(80)
+                       .name(wrap_name_for(&server_id, &program_id))
+                       .height(size.height)
+                       .width(size.width)
                        .margin(1)
                        .build();
         self.layout.tree_mut().root_mut().append(wrap);
         self.layout.flush_changes();
+        self.update_subscriptions();
 
         self.tx.send(ClientMsg::LayoutDamage).unwrap();
     }
 
+    /// Subscribe to programs that are on screen and unsubscribe from the rest, so servers don't
+    /// send damage nobody will see. Call it whenever the layout changes.
+    fn update_subscriptions(&mut self) {
+        let screen_rect = Rect::new(Pos::new(0, 0), self.layout.size.clone());
+        let visible: Vec<String> = self.layout
+                                       .tree()
+                                       .values()
+                                       .filter(|w| {
+                                           match (w.computed_x(), w.computed_y()) {
+                                               (Some(x), Some(y)) => {
+                                                   screen_rect.contains(&Pos::new(x, y))
+                                               }
+                                               _ => false,
+                                           }
+                                       })
+                                       .map(|w| w.name().clone())
+                                       .collect();
+
+        for server in self.servers.iter_mut().filter(|s| s.is_connected) {
+            for program in server.programs.iter_mut() {
+                let wrap_name = wrap_name_for(&server.id, &program.id);
+                let is_visible = visible.iter().any(|n| *n == wrap_name);
+                if is_visible == program.is_subscribed {
+                    continue;
+                }
+
+                let msg = if is_visible {
+                    ::server::ServerMsg::ProgramSubscribe {
+                        client_id: server.client_id.clone(),
+                        program_id: program.id.clone(),
+                    }
+                } else {
+                    ::server::ServerMsg::ProgramUnsubscribe {
+                        client_id: server.client_id.clone(),
+                        program_id: program.id.clone(),
+                    }
+                };
+                if let Err(e) = server.tx.send(msg) {
+                    warn!("couldnt update subscription to {:?}: {}", program.id, e);
+                    continue;
+                }
+                program.is_subscribed = is_visible;
+            }
+        }
+    }
+
+    fn program_exited(&mut self, server_id: String, program_id: String, status: u8) {
+        info!("program {:?} on {:?} exited with {}", program_id, server_id, status);
+        if let Some(program) = self.servers.find_program_mut(&server_id, &program_id) {
+            program.exit_status = Some(status);
+        }
+        self.layout_damage();
+    }
+
+    fn program_cursor_style(&mut self, server_id: String, program_id: String, style: u8) {
+        if let Some(program) = self.servers.find_program_mut(&server_id, &program_id) {
+            program.cursor_style = style;
+        } else {
+            warn!("cursor style for unknown program {:?} on {:?}", program_id, server_id);
+            return;
+        }
+        self.update_outer_cursor_style();
+    }
+
+    /// A flag for silence that's no longer being watched for is cleared
+    fn program_monitor_silence(&mut self, server_id: String, program_id: String, is_on: bool) {
+        info!("monitoring program {:?} on {:?} for silence: {}", program_id, server_id, is_on);
+        let had_silence = match self.servers.find_program_mut(&server_id, &program_id) {
+            Some(program) => {
+                program.monitor_silence = is_on;
+                let had_silence = program.has_silence;
+                if !is_on {
+                    program.has_silence = false;
+                }
+                had_silence
+            }
+            None => {
+                warn!("monitoring unknown program {:?} on {:?}", program_id, server_id);
+                return;
+            }
+        };
+        if had_silence && !is_on {
+            self.layout_damage();
+            self.damage_status_line();
+        }
+    }
+
+    /// The title goes over the program's pane, and in the status line when it is selected
+    fn program_title(&mut self,
+                     server_id: String,
+                     program_id: String,
+                     title: String,
+                     icon_name: String) {
+        trace!("program {:?} on {:?} has the title {:?}", program_id, server_id, title);
+        if let Some(program) = self.servers.find_program_mut(&server_id, &program_id) {
+            program.title = title;
+            program.icon_name = icon_name;
+        } else {
+            warn!("title for unknown program {:?} on {:?}", program_id, server_id);
+            return;
+        }
+        self.layout_damage();
+        self.damage_status_line();
+    }
+
+    fn program_bell(&mut self, server_id: String, program_id: String) {
+        if self.config.forward_bell {
+            self.painter.bell();
+        }
+        self.mark_program(server_id, program_id, |p| p.has_bell = true);
+    }
+
+    /// Programs other than the selected one are flagged, over their pane and in the status line,
+    /// until they are selected
+    fn mark_program<G>(&mut self, server_id: String, program_id: String, mark: G)
+        where G: FnOnce(&mut Program)
+    {
+        let wrap_name = wrap_name_for(&server_id, &program_id);
+        if self.selected_wrap_name.as_ref() == Some(&wrap_name) {
+            return;
+        }
+
+        match self.servers.find_program_mut(&server_id, &program_id) {
+            Some(program) => mark(program),
+            None => return,
+        }
+        self.layout_damage();
+        self.damage_status_line();
+    }
+
+    /// The program has been restarted
+    fn program_update(&mut self, server_id: String, program_id: String) {
+        if let Some(program) = self.servers.find_program_mut(&server_id, &program_id) {
+            program.exit_status = None;
+        }
+        self.layout_damage();
+    }
+
+    fn remove_program(&mut self, server_id: String, program_id: String) {
+        info!("removing program {:?} on {:?}", program_id, server_id);
+        let wrap_name = wrap_name_for(&server_id, &program_id);
+
+        self.servers.remove_program(&server_id, &program_id);
+        self.layout.remove_wrap(&wrap_name);
+        self.layout.flush_changes();
+
+        if self.copy_mode.as_ref().map_or(false, |c| c.wrap_name == wrap_name) {
+            self.copy_mode = None;
+        }
+
+        if self.selected_wrap_name.as_ref() == Some(&wrap_name) {
+            self.selected_wrap_name = None;
+            if self.leaf_names().len() > 0 {
+                self.program_select_next();
+            }
+        }
+
+        self.update_subscriptions();
+        self.repaint();
+    }
+
+    /// The program's screen is a new size, so its pane is too
+    fn program_resize(&mut self, server_id: String, program_id: String, size: Size) {
+        let wrap_name = wrap_name_for(&server_id, &program_id);
+        trace!("program_resize {:?} to {:?}", wrap_name, size);
+
+        if !self.set_wrap_size(&wrap_name, &size) {
+            return;
+        }
+
+        self.layout.flush_changes();
+        self.update_subscriptions();
+        self.repaint();
+    }
+
+    /// Returns whether the wrap changed size. Doesn't flush the layout.
+    fn set_wrap_size(&mut self, wrap_name: &str, size: &Size) -> bool {
+        let mut changed = false;
+        let mut found = false;
+        for wrap in self.layout.tree_mut().values_mut().filter(|w| *w.name() == wrap_name) {
+            found = true;
+            if wrap.width() == Some(size.width) && wrap.height() == Some(size.height) {
+                continue;
+            }
+            wrap.set_width(Some(size.width));
+            wrap.set_height(Some(size.height));
+            changed = true;
+        }
+
+        if !found {
+            warn!("didnt find node with value: {:?}", wrap_name);
+        }
+        changed
+    }
+
+    /// The outer terminal was resized. Everything is laid out again at the new size, programs are
+    /// resized to fit their panes, and the whole screen is repainted.
+    fn tty_resize(&mut self, size: Size) {
+        info!("tty_resize {:?}", size);
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        self.tty_ioctl_config.cols = size.width;
+        self.tty_ioctl_config.rows = size.height;
+        self.screen.resize(size.clone());
+        self.painter.resize(size.clone());
+        self.layout.size = size;
+        // panes' widths are a share of the screen's, so they're worked out before programs are
+        // sized to fit them
+        self.layout.flush_changes();
+
+        let mut resizes = vec![];
+        for server in self.servers.iter().filter(|s| s.is_connected) {
+            for program in server.programs.iter() {
+                let wrap_name = wrap_name_for(&server.id, &program.id);
+                if let Some(size) = self.pane_size(&wrap_name) {
+                    resizes.push((server.id.clone(), program.id.clone(), server.tx.clone(), size));
+                }
+            }
+        }
+
+        for (server_id, program_id, tx, program_size) in resizes {
+            self.set_wrap_size(&wrap_name_for(&server_id, &program_id), &program_size);
+
+            let msg = ::server::ServerMsg::ProgramResize {
+                program_id: program_id,
+                size: program_size,
+            };
+            if let Err(e) = tx.send(msg) {
+                warn!("couldnt resize program on server {:?}: {}", server_id, e);
+            }
+        }
+
+        self.layout.flush_changes();
+        self.update_subscriptions();
+        self.repaint();
+    }
+
+    /// The size a program fits its pane at: the pane's share of the screen's width, and the
+    /// screen's height without the status line, both less the pane's margin or border.
+    fn pane_size(&self, wrap_name: &str) -> Option<Size> {
+        let wrap = match self.layout.tree().values().find(|w| *w.name() == wrap_name) {
+            Some(wrap) => wrap,
+            None => {
+                warn!("didnt find node with value: {:?}", wrap_name);
+                return None;
+            }
+        };
+
+        let width = wrap.computed_width().unwrap_or(0);
+        let inset = wrap.outside_width().unwrap_or(0) - width;
+        let height = self.layout.size.height.saturating_sub(1 + inset);
+        Some(Size::new(cmp::max(width, 1), cmp::max(height, 1)))
+    }
+
+    /// Draw everything from scratch. Programs are asked to send their screens again, since the
+    /// client only keeps what's on screen.
+    fn repaint(&mut self) {
+        for pair in self.screen.iter_mut() {
+            pair.0.clear();
+        }
+        self.layout_damage();
+        self.damage_status_line();
+        self.draw_copy_mode();
+
+        for server in self.servers.iter().filter(|s| s.is_connected) {
+            for program in server.programs.iter().filter(|p| p.is_subscribed) {
+                let wrap_name = wrap_name_for(&server.id, &program.id);
+                let wrap = match self.layout.tree().values().find(|w| *w.name() == wrap_name) {
+                    Some(wrap) => wrap,
+                    None => continue,
+                };
+
+                let msg = ::server::ServerMsg::ProgramRedrawRect {
+                    program_id: program.id.clone(),
+                    rect: Rect::new(Pos::new(0, 0),
+                                    Size::new(wrap.computed_width().unwrap_or(0),
+                                              wrap.computed_height().unwrap_or(0))),
+                };
+                if let Err(e) = server.tx.send(msg) {
+                    warn!("couldnt ask server {:?} for a redraw: {}", server.id, e);
+                }
+            }
+        }
+    }
+
     fn damage_status_line(&mut self) {
         trace!("damage_status_line for mode {:?}",
                self.modal_key_handler.mode_name());
@@ -304,7 +1290,8 @@ impl<F: 'static + Write + Send> MainWorker<F> {
                 cell.chars.clear();
             }
 
-            for (pos, ch) in rect.positions().zip(self.modal_key_handler.mode_name().chars()) {
+            let text = self.status_line_text();
+            for (pos, ch) in rect.positions().zip(text.chars()) {
                 // TODO: find a better way to convert from a char to Vec<u8>. Maybe encode_utf8?
                 let mut sigh = String::new();
                 sigh.push(ch);
@@ -319,6 +1306,92 @@ impl<F: 'static + Write + Send> MainWorker<F> {
         }
     }
 
+    /// The mode name, the selected program's title and any flagged programs, then which server new
+    /// programs start on if there is a choice, and whether any servers have gone away
+    fn status_line_text(&self) -> String {
+        if self.modal_key_handler.mode_name() == "prompt" {
+            return format!("run: {}", self.prompt);
+        }
+        if self.modal_key_handler.mode_name() == "search" {
+            if let Some(text) = self.copy_mode.as_ref().and_then(|c| c.search_text()) {
+                return text;
+            }
+        }
+
+        let mut text = self.modal_key_handler.mode_name().to_string();
+
+        if let Some(copy_mode) = self.copy_mode.as_ref() {
+            text.push_str(&format!(" {}", copy_mode.position_text()));
+        }
+
+        let title = self.selected_title();
+        if !title.is_empty() {
+            text.push_str(&format!("  {}", title));
+        }
+
+        for program in self.servers.iter().flat_map(|s| s.programs.iter()) {
+            let flags = program.flags();
+            if flags.is_empty() {
+                continue;
+            }
+            let name: &str = if program.display_title().is_empty() {
+                &program.id
+            } else {
+                program.display_title()
+            };
+            text.push_str(&format!("  {}{}", flags, name));
+        }
+
+        if self.servers.len() > 1 {
+            if let Some(server_id) = self.selected_server_id.as_ref() {
+                text.push_str(&format!("  new programs on {}", server_id));
+            }
+        }
+
+        let disconnected = self.servers.iter().filter(|s| !s.is_connected).count();
+        if disconnected > 0 {
+            text.push_str(&format!("  {} disconnected", disconnected));
+        }
+
+        text
+    }
+
+    /// The selected program's title, or an empty string if it hasn't set one
+    fn selected_title(&self) -> String {
+        self.selected_wrap_name
+            .as_ref()
+            .and_then(|wrap_name| self.servers.find_by_wrap_name(wrap_name))
+            .map_or(String::new(), |(_, program)| program.display_title().to_string())
+    }
+
+    /// The terminal's cursor looks like the selected program's
+    fn update_outer_cursor_style(&mut self) {
+        let style = self.selected_wrap_name
+                        .as_ref()
+                        .and_then(|wrap_name| self.servers.find_by_wrap_name(wrap_name))
+                        .map_or(0, |(_, program)| program.cursor_style);
+        if style != self.outer_cursor_style {
+            self.painter.set_cursor_style(style);
+            self.outer_cursor_style = style;
+        }
+    }
+
+    /// The terminal's title follows the selected program's, if the user wants that
+    fn update_outer_title(&mut self) {
+        if !self.config.set_title {
+            return;
+        }
+
+        let mut title = self.selected_title();
+        if title.is_empty() {
+            title = "intermix".to_string();
+        }
+        if self.outer_title.as_ref() != Some(&title) {
+            self.painter.set_title(&title);
+            self.outer_title = Some(title);
+        }
+    }
+
     fn mode_change(&mut self, _: &str) {
         self.damage_status_line();
     }
@@ -333,14 +1406,25 @@ impl<F: 'static + Write + Send> MainWorker<F> {
     }
 
     fn program_damage(&mut self,
+                      server_id: String,
                       program_id: String,
                       cells: Vec<vterm_sys::ScreenCell>,
                       rect: vterm_sys::Rect) {
-        trace!("program_damage for {}", program_id);
+        trace!("program_damage for {} on {}", program_id, server_id);
+
+        let wrap_name = wrap_name_for(&server_id, &program_id);
+        if self.copy_mode.as_ref().map_or(false, |c| c.wrap_name == wrap_name) {
+            // the pane shows copy mode until it exits, which asks for a redraw
+            return;
+        }
 
-        if let Some(wrap) = self.layout.tree().values().find(|w| *w.name() == program_id) {
+        if let Some(wrap) = self.layout.tree().values().find(|w| *w.name() == wrap_name) {
+            let screen_rect = Rect::new(Pos::new(0, 0), self.layout.size.clone());
             for (vterm_cell, pos) in cells.iter().zip(rect.positions()) {
                 let pos = pos + Pos::new(wrap.computed_x().unwrap(), wrap.computed_y().unwrap());
+                if !screen_rect.contains(&pos) {
+                    continue;
+                }
                 let mut cell = self.screen.index_mut(pos);
 
                 // TODO: make the wire data format be the same as Cell so this is just a memcopy
@@ -351,7 +1435,7 @@ impl<F: 'static + Write + Send> MainWorker<F> {
 
             self.painter.draw_screen(&mut self.screen);
         } else {
-            warn!("didnt find node with value: {:?}", program_id);
+            warn!("didnt find node with value: {:?}", wrap_name);
         }
     }
 
@@ -367,13 +1451,27 @@ impl<F: 'static + Write + Send> MainWorker<F> {
         trace!("layout_damage");
 
         for wrap in self.layout.tree().values() {
-            MainWorker::draw_node_box(&mut self.screen, wrap, &mut self.painter, &self.layout.size);
+            let label = self.servers
+                            .find_by_wrap_name(wrap.name())
+                            .and_then(|(_, program)| pane_label(program));
+            MainWorker::draw_node_box(&mut self.screen,
+                                      wrap,
+                                      label,
+                                      &mut self.painter,
+                                      &self.layout.size);
         }
+
+        // the selection or the selected program's title may have changed
+        self.update_outer_title();
+        self.update_outer_cursor_style();
+        self.place_cursor();
     }
 
-    /// Draw any margin border or padding for the given node
+    /// Draw any margin border or padding for the given node. The label is written over the top
+    /// edge, whether that's the border or the margin.
     fn draw_node_box(screen: &mut CellBuffer,
                      wrap: &layout::Wrap,
+                     label: Option<String>,
                      painter: &mut TtyPainter<F>,
                      size: &Size) {
         let screen_rect = Rect::new(Pos::new(0, 0), size.clone());
@@ -382,13 +1480,17 @@ impl<F: 'static + Write + Send> MainWorker<F> {
                                               wrap.outside_y().unwrap()),
                                      Size::new(wrap.outside_width().unwrap(),
                                                wrap.outside_height().unwrap()));
-        let outside_rect = outside_rect.intersection(&screen_rect).unwrap();
+        // nothing to draw if the wrap is off screen, for example after the terminal shrinks
+        let outside_rect = match outside_rect.intersection(&screen_rect) {
+            Some(rect) => rect,
+            None => return,
+        };
 
         let inside_rect = Rect::new(Pos::new(wrap.computed_x().unwrap(),
                                              wrap.computed_y().unwrap()),
                                     Size::new(wrap.computed_width().unwrap(),
                                               wrap.computed_height().unwrap()));
-        let inside_rect = inside_rect.intersection(&screen_rect).unwrap();
+        let inside_rect = inside_rect.intersection(&screen_rect).unwrap_or(inside_rect);
 
         for pos in outside_rect.positions().filter(|p| !inside_rect.contains(p)) {
             screen.index_mut(pos).clear();
@@ -399,7 +1501,10 @@ impl<F: 'static + Write + Send> MainWorker<F> {
                                                  wrap.border_y().unwrap()),
                                         Size::new(wrap.border_width().unwrap(),
                                                   wrap.border_height().unwrap()));
-            let border_rect = border_rect.intersection(&screen_rect).unwrap();
+            let border_rect = match border_rect.intersection(&screen_rect) {
+                Some(rect) => rect,
+                None => return,
+            };
 
             let top_and_bottoms = border_rect.positions().filter(|p| {
                 p.y == border_rect.min_y() || p.y == border_rect.max_y() - 1
@@ -427,16 +1532,186 @@ impl<F: 'static + Write + Send> MainWorker<F> {
                   .chars = "┘".to_string().into_bytes();
         }
 
+        if let Some(label) = label {
+            if outside_rect.origin.y < inside_rect.origin.y {
+                let y = outside_rect.origin.y;
+                // long labels are cut short of the top right corner
+                let xs = (outside_rect.min_x() + 1)..outside_rect.max_x().saturating_sub(1);
+                for (x, ch) in xs.zip(label.chars()) {
+                    let cell = screen.index_mut(Pos::new(x, y));
+                    cell.chars = ch.to_string().into_bytes();
+                    cell.dirty = true;
+                }
+            }
+        }
+
         painter.draw_screen(screen);
     }
 
-    fn move_cursor(&mut self, program_id: String, pos: vterm_sys::Pos, is_visible: bool) {
-        if let Some(wrap) = self.layout.tree().values().find(|w| *w.name() == program_id) {
-            let pos = Pos::new(pos.x + wrap.computed_x().unwrap(),
-                               pos.y + wrap.computed_y().unwrap());
-            self.painter.move_cursor(pos, is_visible);
-        } else {
-            warn!("didnt find node with value: {:?}", program_id);
+    /// Programs that aren't selected only have their cursor remembered, so output in the
+    /// background doesn't take the cursor away from the pane the user is typing into
+    fn move_cursor(&mut self,
+                   server_id: String,
+                   program_id: String,
+                   pos: vterm_sys::Pos,
+                   is_visible: bool) {
+        match self.servers.find_program_mut(&server_id, &program_id) {
+            Some(program) => {
+                program.cursor_pos = Some(pos);
+                program.is_cursor_hidden = !is_visible;
+            }
+            None => {
+                warn!("cursor move for unknown program {:?} on {:?}", program_id, server_id);
+                return;
+            }
+        }
+
+        if self.selected_wrap_name == Some(wrap_name_for(&server_id, &program_id)) {
+            self.place_cursor();
+        }
+    }
+
+    /// Put the terminal's cursor where the selected program's is. It's hidden if the program hid
+    /// it, or its pane is in copy mode, which draws a cursor of its own, or nothing is selected.
+    fn place_cursor(&mut self) {
+        let selected = self.selected_wrap_name.as_ref();
+        let in_copy_mode = self.copy_mode
+                               .as_ref()
+                               .map_or(false, |c| Some(&c.wrap_name) == selected);
+        let cursor = match self.selected_wrap_name.as_ref() {
+            Some(wrap_name) if !in_copy_mode => {
+                let program = self.servers.find_by_wrap_name(wrap_name).map(|(_, p)| p);
+                let wrap = self.layout.tree().values().find(|w| *w.name() == *wrap_name);
+                match (program, wrap) {
+                    (Some(program), Some(wrap)) => {
+                        program.cursor_pos.as_ref().map(|pos| {
+                            (Pos::new(pos.x + wrap.computed_x().unwrap(),
+                                      pos.y + wrap.computed_y().unwrap()),
+                             !program.is_cursor_hidden)
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let screen_rect = Rect::new(Pos::new(0, 0), self.layout.size.clone());
+        match cursor {
+            Some((pos, is_visible)) if screen_rect.contains(&pos) => {
+                self.painter.move_cursor(pos, is_visible)
+            }
+            _ => self.painter.hide_cursor(),
+        }
+    }
+}
+
+/// The program's flags and title, and its exit status once it has exited
+fn pane_label(program: &Program) -> Option<String> {
+    let mut parts = vec![];
+    if !program.flags().is_empty() {
+        parts.push(program.flags());
+    }
+    if !program.display_title().is_empty() {
+        parts.push(program.display_title().to_string());
+    }
+    if let Some(status) = program.exit_status {
+        parts.push(format!("exited {}", status));
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!(" {} ", parts.join(" - ")))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use client::{TtyIoCtlConfig, ClientConfig};
+    use client::layout;
+    use client::servers::*;
+    use std::env;
+    use std::io::{self, Write};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+    use transport::Tx;
+    use vterm_sys::{Pos, Size};
+
+    /// Collects what the worker paints
+    struct SharedIo {
+        bytes: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for SharedIo {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.bytes.lock().unwrap().extend(buf.iter());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
         }
     }
+
+    /// A worker with programs 1 and 2 on server s, and what it has painted so far taken away
+    fn worker_with_programs() -> (MainWorker<SharedIo>, Arc<Mutex<Vec<u8>>>) {
+        env::set_var("TERM", "xterm");
+        let (tx, rx) = channel();
+        let (server_tx, _) = channel();
+        let bytes = Arc::new(Mutex::new(vec![]));
+        let mut worker = MainWorker::new(rx,
+                                         tx,
+                                         TtyIoCtlConfig { rows: 24, cols: 80 },
+                                         ClientConfig::default(),
+                                         layout::Layout::new(Size::new(80, 24)),
+                                         SharedIo { bytes: bytes.clone() });
+        worker.add_server(Server::new("s", "a", Tx::Channel(server_tx)));
+        worker.add_program("s".to_string(), "1".to_string(), Size::new(10, 5));
+        worker.add_program("s".to_string(), "2".to_string(), Size::new(10, 5));
+        bytes.lock().unwrap().clear();
+        (worker, bytes)
+    }
+
+    fn wrap_pos(worker: &MainWorker<SharedIo>, program_id: &str) -> Pos {
+        let wrap_name = wrap_name_for("s", program_id);
+        let wrap = worker.layout.tree().values().find(|w| *w.name() == wrap_name).unwrap();
+        Pos::new(wrap.computed_x().unwrap(), wrap.computed_y().unwrap())
+    }
+
+    #[test]
+    fn it_places_the_cursor_where_the_selected_program_has_it() {
+        let (mut worker, bytes) = worker_with_programs();
+        worker.selected_wrap_name = Some(wrap_name_for("s", "1"));
+
+        worker.move_cursor("s".to_string(), "1".to_string(), Pos::new(2, 1), true);
+
+        let pos = wrap_pos(&worker, "1");
+        let cup = format!("\x1b[{};{}H", pos.y + 1 + 1, pos.x + 2 + 1).into_bytes();
+        let painted = bytes.lock().unwrap().clone();
+        assert!(painted.windows(cup.len()).any(|w| w == &cup[..]));
+    }
+
+    #[test]
+    fn it_leaves_the_cursor_alone_when_other_programs_move_theirs() {
+        let (mut worker, bytes) = worker_with_programs();
+        worker.selected_wrap_name = Some(wrap_name_for("s", "2"));
+
+        worker.move_cursor("s".to_string(), "1".to_string(), Pos::new(2, 1), true);
+
+        assert!(bytes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_hides_the_cursor_when_no_program_is_selected() {
+        let (mut worker, bytes) = worker_with_programs();
+        worker.move_cursor("s".to_string(), "1".to_string(), Pos::new(2, 1), true);
+
+        worker.place_cursor();
+
+        let civis = b"\x1b[?25l";
+        let painted = bytes.lock().unwrap().clone();
+        assert!(painted.windows(civis.len()).any(|w| w == &civis[..]));
+    }
 }
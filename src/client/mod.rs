@@ -1,15 +1,19 @@
 pub mod paint;
 pub mod layout;
+pub mod copy_mode;
 pub mod main_worker;
 pub mod modal;
+pub mod mouse;
 pub mod servers;
 pub mod stdin_read_worker;
+pub mod winch_worker;
 
 use self::main_worker::*;
 use self::servers::*;
 use self::stdin_read_worker::*;
 use std::io::prelude::*;
 use std::sync::mpsc::*;
+use std::thread::JoinHandle;
 use vterm_sys;
 
 #[derive(Clone, Debug)]
@@ -29,6 +33,7 @@ pub enum ClientMsg {
     ProgramAdd {
         server_id: String,
         program_id: String,
+        size: vterm_sys::Size,
     },
     ProgramUpdate {
         server_id: String,
@@ -38,12 +43,72 @@ pub enum ClientMsg {
         server_id: String,
         program_id: String,
     },
+    ProgramResize {
+        server_id: String,
+        program_id: String,
+        size: vterm_sys::Size,
+    },
+    /// status is the exit code, or 128 plus the signal that killed it
+    ProgramExited {
+        server_id: String,
+        program_id: String,
+        status: u8,
+    },
+    /// Lines from a program's scrollback, in reply to ProgramScrollbackFetch. total is how many
+    /// lines the server has for it.
+    ProgramScrollback {
+        server_id: String,
+        program_id: String,
+        start: usize,
+        lines: Vec<Vec<vterm_sys::ScreenCell>>,
+        total: usize,
+    },
+    /// A program set the clipboard. The server has put it in its clipboard paste buffer.
+    Clipboard {
+        server_id: String,
+        bytes: Vec<u8>,
+    },
+    /// What a program has set its title and icon name to. Both are empty until it sets them.
+    ProgramTitle {
+        server_id: String,
+        program_id: String,
+        title: String,
+        icon_name: String,
+    },
+    ProgramBell {
+        server_id: String,
+        program_id: String,
+    },
+    /// A program that had been quiet has started writing again
+    ProgramActivity {
+        server_id: String,
+        program_id: String,
+    },
+    /// A program monitored for silence hasn't written anything for the server's silence period
+    ProgramSilence {
+        server_id: String,
+        program_id: String,
+    },
+    /// A program set the cursor style, with DECSCUSR's numbering. 0 is the terminal's default.
+    ProgramCursorStyle {
+        server_id: String,
+        program_id: String,
+        style: u8,
+    },
+    /// Whether a program is monitored for silence, which any client may have changed
+    ProgramMonitorSilence {
+        server_id: String,
+        program_id: String,
+        is_on: bool,
+    },
     ProgramDamage {
+        server_id: String,
         program_id: String,
         cells: Vec<vterm_sys::ScreenCell>,
         rect: vterm_sys::Rect,
     },
     ProgramMoveCursor {
+        server_id: String,
         program_id: String,
         new: vterm_sys::Pos,
         old: vterm_sys::Pos,
@@ -62,6 +127,11 @@ pub enum ClientMsg {
     },
 
     StatusLineDamage,
+
+    /// The outer terminal is now this size
+    TtyResize {
+        size: vterm_sys::Size,
+    },
 }
 
 /// other settings from `man tty_ioctl` could live here
@@ -80,10 +150,36 @@ impl Default for TtyIoCtlConfig {
     }
 }
 
+/// Settings that change how the client treats the terminal it is running in
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// Pass clipboard writes from programs, and copy mode yanks, on to the terminal with OSC 52
+    pub forward_clipboard: bool,
+    /// Have the terminal report the mouse, so clicks focus panes and programs that want the mouse
+    /// get it
+    pub mouse: bool,
+    /// Set the terminal's title to the selected program's title, putting the old one back on exit
+    pub set_title: bool,
+    /// Ring the terminal's bell when a program rings its bell
+    pub forward_bell: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> ClientConfig {
+        ClientConfig {
+            forward_clipboard: false,
+            mouse: false,
+            set_title: false,
+            forward_bell: false,
+        }
+    }
+}
+
 /// # TODO
 /// * [ ] derive useful traits on stuff here
 pub struct Client {
     main_tx: Sender<ClientMsg>,
+    main_handle: JoinHandle<()>,
 }
 
 impl Client {
@@ -93,16 +189,18 @@ impl Client {
     /// All the action takes place in threads so message passing is the client api more or less.
     pub fn spawn<I, O>(input: I,
                        output: O,
-                       tty_ioctl_config: TtyIoCtlConfig)
+                       tty_ioctl_config: TtyIoCtlConfig,
+                       config: ClientConfig)
                        -> (Sender<ClientMsg>, Client)
         where I: 'static + Read + Send,
               O: 'static + Write + Send
     {
-        let (main_tx, _) = MainWorker::spawn(tty_ioctl_config, output);
+        let (main_tx, main_handle) = MainWorker::spawn(tty_ioctl_config, config, output);
         StdinReadWorker::spawn(input, main_tx.clone());
 
         let client = Client {
             main_tx: main_tx.clone(),
+            main_handle: main_handle,
         };
 
         (main_tx, client)
@@ -113,6 +211,11 @@ impl Client {
         self.main_tx.send(ClientMsg::Quit).unwrap();
     }
 
+    /// Block until the client exits, for example because the user quit.
+    pub fn join(self) {
+        self.main_handle.join().expect("main worker wouldn't join");
+    }
+
     pub fn tx(&self) -> &Sender<ClientMsg> {
         &self.main_tx
     }
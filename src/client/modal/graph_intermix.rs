@@ -2,6 +2,7 @@ use super::graph::*;
 use super::modal_key_handler::*;
 
 const CTRL_B: u8 = 2u8;
+const ESC: u8 = 0x1bu8;
 
 pub fn graph() -> Graph<NodeData, EdgeData> {
     let mut graph: Graph<NodeData, EdgeData> = Graph::new();
@@ -9,6 +10,9 @@ pub fn graph() -> Graph<NodeData, EdgeData> {
     let w = graph.add_node(NodeData { name: "welcome".to_string() });
     let c = graph.add_node(NodeData { name: "command".to_string() });
     let p = graph.add_node(NodeData { name: "program".to_string() });
+    let r = graph.add_node(NodeData { name: "prompt".to_string() });
+    let y = graph.add_node(NodeData { name: "copy".to_string() });
+    let f = graph.add_node(NodeData { name: "search".to_string() });
 
     graph.add_edge(w, c, EdgeData { default: true, ..Default::default() });
 
@@ -33,6 +37,55 @@ pub fn graph() -> Graph<NodeData, EdgeData> {
                        codes: "c".to_string().into_bytes(),
                        ..Default::default()
                    });
+    graph.add_edge(c,
+                   y,
+                   EdgeData {
+                       action: Some(ActionType::CopyModeStart),
+                       codes: "[".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::Paste),
+                       codes: "p".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   r,
+                   EdgeData {
+                       action: Some(ActionType::PromptStart),
+                       codes: "C".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::ProgramKill),
+                       codes: "x".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::ProgramRestart),
+                       codes: "r".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::ProgramMonitorSilence),
+                       codes: "~".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::ProgramDismiss),
+                       codes: "X".to_string().into_bytes(),
+                       ..Default::default()
+                   });
     graph.add_edge(c,
                    c,
                    EdgeData {
@@ -40,6 +93,20 @@ pub fn graph() -> Graph<NodeData, EdgeData> {
                        codes: "q".to_string().into_bytes(),
                        ..Default::default()
                    });
+    graph.add_edge(c,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::Detach),
+                       codes: "d".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::ServerSelectNext),
+                       codes: "s".to_string().into_bytes(),
+                       ..Default::default()
+                   });
     graph.add_edge(c,
                    p,
                    EdgeData {
@@ -49,7 +116,107 @@ pub fn graph() -> Graph<NodeData, EdgeData> {
                    });
 
     // ('c', 'program_create_and_focus');
-    // ('x', 'program_kill');
+
+    graph.add_edge(r,
+                   r,
+                   EdgeData {
+                       action: Some(ActionType::PromptInput),
+                       default: true,
+                       ..Default::default()
+                   });
+    graph.add_edge(r,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::PromptSubmit),
+                       codes: vec![b'\r'],
+                       ..Default::default()
+                   });
+    graph.add_edge(r,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::PromptCancel),
+                       codes: vec![ESC],
+                       ..Default::default()
+                   });
+
+    graph.add_edge(y,
+                   y,
+                   EdgeData {
+                       action: Some(ActionType::CopyModeInput),
+                       default: true,
+                       ..Default::default()
+                   });
+    graph.add_edge(y,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::CopyModeYank),
+                       codes: "y".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(y,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::CopyModeExit),
+                       codes: "q".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(y,
+                   c,
+                   EdgeData {
+                       action: Some(ActionType::CopyModeExit),
+                       codes: vec![ESC],
+                       ..Default::default()
+                   });
+
+    graph.add_edge(c,
+                   f,
+                   EdgeData {
+                       action: Some(ActionType::SearchForward),
+                       codes: "/".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(c,
+                   f,
+                   EdgeData {
+                       action: Some(ActionType::SearchBackward),
+                       codes: "?".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(y,
+                   f,
+                   EdgeData {
+                       action: Some(ActionType::SearchForward),
+                       codes: "/".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(y,
+                   f,
+                   EdgeData {
+                       action: Some(ActionType::SearchBackward),
+                       codes: "?".to_string().into_bytes(),
+                       ..Default::default()
+                   });
+    graph.add_edge(f,
+                   f,
+                   EdgeData {
+                       action: Some(ActionType::SearchInput),
+                       default: true,
+                       ..Default::default()
+                   });
+    graph.add_edge(f,
+                   y,
+                   EdgeData {
+                       action: Some(ActionType::SearchConfirm),
+                       codes: vec![b'\r'],
+                       ..Default::default()
+                   });
+    graph.add_edge(f,
+                   y,
+                   EdgeData {
+                       action: Some(ActionType::SearchCancel),
+                       codes: vec![ESC],
+                       ..Default::default()
+                   });
 
     graph.add_edge(p,
                    c,
@@ -65,6 +232,13 @@ pub fn graph() -> Graph<NodeData, EdgeData> {
                        default: true,
                        ..Default::default()
                    });
+    graph.add_edge(p,
+                   p,
+                   EdgeData {
+                       action: Some(ActionType::Paste),
+                       codes: escape("p".to_string().into_bytes(), CTRL_B),
+                       ..Default::default()
+                   });
     graph.add_edge(p,
                    p,
                    EdgeData {
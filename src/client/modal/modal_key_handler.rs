@@ -9,22 +9,54 @@ pub struct NodeData {
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum ActionType {
+    CopyModeExit,
+    CopyModeInput,
+    CopyModeStart,
+    CopyModeYank,
+    Detach,
+    Paste,
+    ProgramDismiss,
     ProgramFocus,
     ProgramInput,
+    ProgramKill,
+    ProgramMonitorSilence,
+    ProgramRestart,
     ProgramStart,
     ProgramSelectNext,
     ProgramSelectPrev,
+    PromptCancel,
+    PromptInput,
+    PromptStart,
+    PromptSubmit,
     Quit,
+    SearchBackward,
+    SearchCancel,
+    SearchConfirm,
+    SearchForward,
+    SearchInput,
+    ServerSelectNext,
 }
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum UserAction {
+    CopyModeExit,
+    CopyModeInput {
+        bytes: Vec<u8>,
+    },
+    CopyModeStart,
+    CopyModeYank,
+    Detach,
+    Paste,
     UnknownInput {
         bytes: Vec<u8>,
     },
     ProgramInput {
         bytes: Vec<u8>,
     },
+    ProgramDismiss,
+    ProgramKill,
+    ProgramMonitorSilence,
+    ProgramRestart,
     ProgramStart,
     ProgramFocus,
     ProgramSelectNext,
@@ -32,7 +64,21 @@ pub enum UserAction {
     ModeChange {
         name: String,
     },
+    PromptCancel,
+    PromptInput {
+        bytes: Vec<u8>,
+    },
+    PromptStart,
+    PromptSubmit,
     Quit,
+    SearchBackward,
+    SearchCancel,
+    SearchConfirm,
+    SearchForward,
+    SearchInput {
+        bytes: Vec<u8>,
+    },
+    ServerSelectNext,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -115,12 +161,32 @@ impl Write for ModalKeyHandler {
 
             if let Some(a) = action {
                 let user_action = match a {
+                    ActionType::CopyModeExit => UserAction::CopyModeExit,
+                    ActionType::CopyModeInput => UserAction::CopyModeInput { bytes: match_buf },
+                    ActionType::CopyModeStart => UserAction::CopyModeStart,
+                    ActionType::CopyModeYank => UserAction::CopyModeYank,
+                    ActionType::Detach => UserAction::Detach,
+                    ActionType::Paste => UserAction::Paste,
+                    ActionType::ProgramDismiss => UserAction::ProgramDismiss,
                     ActionType::ProgramInput => UserAction::ProgramInput { bytes: match_buf },
+                    ActionType::ProgramKill => UserAction::ProgramKill,
+                    ActionType::ProgramMonitorSilence => UserAction::ProgramMonitorSilence,
+                    ActionType::ProgramRestart => UserAction::ProgramRestart,
                     ActionType::ProgramStart => UserAction::ProgramStart,
                     ActionType::ProgramFocus => UserAction::ProgramFocus,
                     ActionType::ProgramSelectPrev => UserAction::ProgramSelectPrev,
                     ActionType::ProgramSelectNext => UserAction::ProgramSelectNext,
+                    ActionType::PromptCancel => UserAction::PromptCancel,
+                    ActionType::PromptInput => UserAction::PromptInput { bytes: match_buf },
+                    ActionType::PromptStart => UserAction::PromptStart,
+                    ActionType::PromptSubmit => UserAction::PromptSubmit,
                     ActionType::Quit => UserAction::Quit,
+                    ActionType::SearchBackward => UserAction::SearchBackward,
+                    ActionType::SearchCancel => UserAction::SearchCancel,
+                    ActionType::SearchConfirm => UserAction::SearchConfirm,
+                    ActionType::SearchForward => UserAction::SearchForward,
+                    ActionType::SearchInput => UserAction::SearchInput { bytes: match_buf },
+                    ActionType::ServerSelectNext => UserAction::ServerSelectNext,
                 };
                 self.actions_queue.push(user_action);
             }
@@ -323,6 +389,33 @@ mod tests {
                    Some(&UserAction::ProgramInput { bytes: "a".to_string().into_bytes() }));
     }
 
+    #[test]
+    fn typing_a_command_at_the_prompt_queues_it_in_order() {
+        let mut h = ModalKeyHandler::new_with_graph();
+
+        // leave the welcome mode
+        h.write(" ".as_bytes()).unwrap();
+        h.actions_queue.clear();
+
+        h.write("Cls\r".as_bytes()).unwrap();
+        let actions: Vec<UserAction> = h.actions_queue
+                                        .iter()
+                                        .filter(|a| {
+                                            match **a {
+                                                UserAction::ModeChange { .. } => false,
+                                                _ => true,
+                                            }
+                                        })
+                                        .cloned()
+                                        .collect();
+        assert_eq!(actions,
+                   vec![UserAction::PromptStart,
+                        UserAction::PromptInput { bytes: vec![b'l'] },
+                        UserAction::PromptInput { bytes: vec![b's'] },
+                        UserAction::PromptSubmit]);
+        assert_eq!(h.mode_name(), "command");
+    }
+
     #[test]
     fn when_matching_edge_has_a_program_start_action_it_adds_to_queue() {
         let mut graph: Graph<NodeData, EdgeData> = Graph::new();
@@ -356,4 +449,38 @@ mod tests {
         h.write("a".as_bytes()).unwrap();
         assert_eq!(h.actions_queue.first(), Some(&UserAction::Quit));
     }
+
+    #[test]
+    fn when_matching_edge_has_a_detach_action_it_adds_to_queue() {
+        let mut graph: Graph<NodeData, EdgeData> = Graph::new();
+        let n0_index = graph.add_node(NodeData { name: "n0".to_string() });
+        graph.add_edge(n0_index,
+                       n0_index,
+                       EdgeData {
+                           action: Some(ActionType::Detach),
+                           default: true,
+                           ..Default::default()
+                       });
+        let mut h = ModalKeyHandler::new(n0_index, graph);
+
+        h.write("a".as_bytes()).unwrap();
+        assert_eq!(h.actions_queue.first(), Some(&UserAction::Detach));
+    }
+
+    #[test]
+    fn when_matching_edge_has_a_server_select_next_action_it_adds_to_queue() {
+        let mut graph: Graph<NodeData, EdgeData> = Graph::new();
+        let n0_index = graph.add_node(NodeData { name: "n0".to_string() });
+        graph.add_edge(n0_index,
+                       n0_index,
+                       EdgeData {
+                           action: Some(ActionType::ServerSelectNext),
+                           default: true,
+                           ..Default::default()
+                       });
+        let mut h = ModalKeyHandler::new(n0_index, graph);
+
+        h.write("a".as_bytes()).unwrap();
+        assert_eq!(h.actions_queue.first(), Some(&UserAction::ServerSelectNext));
+    }
 }
@@ -0,0 +1,176 @@
+use vterm_sys::Pos;
+
+const SGR_START: &'static [u8] = b"\x1b[<";
+
+/// The low two bits of the button byte are the button, with 3 meaning none
+const NO_BUTTON: u8 = 3;
+const MOTION: u8 = 32;
+const WHEEL: u8 = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MouseEvent {
+    /// The SGR button byte: the button, plus 4 for shift, 8 for meta, 16 for control, 32 for
+    /// motion and 64 for the wheel
+    pub button: u8,
+    /// 0 based, on the whole screen
+    pub pos: Pos,
+    pub is_release: bool,
+}
+
+impl MouseEvent {
+    /// A button going down, rather than motion, a release or the wheel
+    pub fn is_press(&self) -> bool {
+        !self.is_release && self.button & (MOTION | WHEEL) == 0 && self.button & 3 != NO_BUTTON
+    }
+
+    pub fn is_wheel(&self) -> bool {
+        self.button & WHEEL != 0
+    }
+}
+
+/// Takes the SGR mouse reports out of what the user typed, leaving the rest alone. A report split
+/// across reads is held on to until the rest of it comes, but only once its `ESC [ <` has been
+/// seen, so a lone escape isn't held up.
+pub struct MouseParser {
+    partial: Vec<u8>,
+}
+
+impl MouseParser {
+    pub fn new() -> MouseParser {
+        MouseParser { partial: vec![] }
+    }
+
+    /// Returns the bytes that weren't mouse reports, and the events that were
+    pub fn parse(&mut self, bytes: &[u8]) -> (Vec<u8>, Vec<MouseEvent>) {
+        let mut input = self.partial.clone();
+        input.extend(bytes);
+        self.partial.clear();
+
+        let mut rest = vec![];
+        let mut events = vec![];
+        let mut i = 0;
+        while i < input.len() {
+            if !input[i..].starts_with(SGR_START) {
+                rest.push(input[i]);
+                i += 1;
+                continue;
+            }
+
+            match parse_report(&input[i + SGR_START.len()..]) {
+                Report::Complete(event, len) => {
+                    events.push(event);
+                    i += SGR_START.len() + len;
+                }
+                Report::Partial => {
+                    self.partial.extend(&input[i..]);
+                    break;
+                }
+                Report::Invalid => {
+                    rest.push(input[i]);
+                    i += 1;
+                }
+            }
+        }
+
+        (rest, events)
+    }
+}
+
+enum Report {
+    /// The event, and how many bytes it took up after the `ESC [ <`
+    Complete(MouseEvent, usize),
+    Partial,
+    Invalid,
+}
+
+/// Parses the `b ; x ; y M` or `b ; x ; y m` after `ESC [ <`
+fn parse_report(bytes: &[u8]) -> Report {
+    let mut params = vec![0usize];
+    for (i, byte) in bytes.iter().enumerate() {
+        match *byte {
+            b'0'...b'9' => {
+                let param = params.last_mut().unwrap();
+                *param = param.saturating_mul(10).saturating_add((byte - b'0') as usize);
+            }
+            b';' if params.len() < 3 => params.push(0),
+            b'M' | b'm' if params.len() == 3 => {
+                if params[1] == 0 || params[2] == 0 || params[0] > 255 {
+                    return Report::Invalid;
+                }
+                let event = MouseEvent {
+                    button: params[0] as u8,
+                    pos: Pos::new(params[1] - 1, params[2] - 1),
+                    is_release: *byte == b'm',
+                };
+                return Report::Complete(event, i + 1);
+            }
+            _ => return Report::Invalid,
+        }
+    }
+
+    Report::Partial
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use vterm_sys::Pos;
+
+    #[test]
+    fn it_takes_out_mouse_reports() {
+        let mut parser = MouseParser::new();
+
+        let (rest, events) = parser.parse(b"a\x1b[<0;3;2Mb\x1b[<0;3;2mc");
+
+        assert_eq!(rest, b"abc".to_vec());
+        assert_eq!(events,
+                   vec![MouseEvent {
+                            button: 0,
+                            pos: Pos::new(2, 1),
+                            is_release: false,
+                        },
+                        MouseEvent {
+                            button: 0,
+                            pos: Pos::new(2, 1),
+                            is_release: true,
+                        }]);
+    }
+
+    #[test]
+    fn it_holds_on_to_reports_split_across_reads() {
+        let mut parser = MouseParser::new();
+
+        assert_eq!(parser.parse(b"x\x1b[<64;1"), (b"x".to_vec(), vec![]));
+        let (rest, events) = parser.parse(b"0;20M");
+
+        assert!(rest.is_empty());
+        assert_eq!(events.len(), 1);
+        assert!(events[0].is_wheel());
+        assert_eq!(events[0].pos, Pos::new(9, 19));
+    }
+
+    #[test]
+    fn it_passes_on_other_escape_sequences() {
+        let mut parser = MouseParser::new();
+
+        assert_eq!(parser.parse(b"\x1b"), (b"\x1b".to_vec(), vec![]));
+        assert_eq!(parser.parse(b"\x1b[A\x1b[<1;x"), (b"\x1b[A\x1b[<1;x".to_vec(), vec![]));
+    }
+
+    #[test]
+    fn it_tells_presses_from_motion_and_releases() {
+        let event = |button, is_release| {
+            MouseEvent {
+                button: button,
+                pos: Pos::new(0, 0),
+                is_release: is_release,
+            }
+        };
+
+        assert!(event(0, false).is_press());
+        assert!(event(16 + 2, false).is_press());
+        assert!(!event(0, true).is_press());
+        assert!(!event(32, false).is_press());
+        assert!(!event(64, false).is_press());
+    }
+}
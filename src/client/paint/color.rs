@@ -0,0 +1,119 @@
+use term::terminfo::TermInfo;
+use vterm_sys::ColorRGB;
+
+/// How the outer terminal can be told what color to use
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSupport {
+    Colors16,
+    Colors256,
+    /// 24 bit color, with `ESC [ 38 ; 2 ; r ; g ; b m` and its background twin
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Terminals that do 24 bit color either say so with COLORTERM, or have the Tc or RGB flags in
+    /// their terminfo. Otherwise it's down to how many colors terminfo says there are.
+    pub fn detect(terminfo: &TermInfo, colorterm: Option<&str>) -> ColorSupport {
+        let is_true_color = match colorterm {
+            Some("truecolor") | Some("24bit") => true,
+            _ => {
+                terminfo.bools.get("Tc").cloned().unwrap_or(false) ||
+                terminfo.bools.get("RGB").cloned().unwrap_or(false)
+            }
+        };
+
+        if is_true_color {
+            ColorSupport::TrueColor
+        } else if terminfo.numbers.get("colors").map_or(false, |n| *n >= 256) {
+            ColorSupport::Colors256
+        } else {
+            ColorSupport::Colors16
+        }
+    }
+}
+
+/// The colors xterm uses for the 16 ansi colors, which most terminals are close to
+const XTERM_COLORS: [(u8, u8, u8); 16] = [(0, 0, 0),
+                                          (205, 0, 0),
+                                          (0, 205, 0),
+                                          (205, 205, 0),
+                                          (0, 0, 238),
+                                          (205, 0, 205),
+                                          (0, 205, 205),
+                                          (229, 229, 229),
+                                          (127, 127, 127),
+                                          (255, 0, 0),
+                                          (0, 255, 0),
+                                          (255, 255, 0),
+                                          (92, 92, 255),
+                                          (255, 0, 255),
+                                          (0, 255, 255),
+                                          (255, 255, 255)];
+
+/// The levels of each channel in xterm's 6x6x6 color cube
+const XTERM_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(rgb: &ColorRGB, other: (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32) * (a as i32 - b as i32);
+    (d(rgb.red, other.0) + d(rgb.green, other.1) + d(rgb.blue, other.2)) as u32
+}
+
+/// The closest color in the 256 color cube or grey ramp. The first 16 colors are left out since
+/// terminal themes change them.
+pub fn nearest_256(rgb: &ColorRGB) -> u8 {
+    let nearest_level = |c: u8| {
+        (0..6).min_by_key(|i| (XTERM_CUBE_LEVELS[*i] as i32 - c as i32).abs()).unwrap()
+    };
+    let (r, g, b) = (nearest_level(rgb.red), nearest_level(rgb.green), nearest_level(rgb.blue));
+    let cube_index = 16 + 36 * r + 6 * g + b;
+    let cube = (XTERM_CUBE_LEVELS[r], XTERM_CUBE_LEVELS[g], XTERM_CUBE_LEVELS[b]);
+
+    let grey_index = (0..24).min_by_key(|i| distance(rgb, grey(*i))).unwrap();
+
+    if distance(rgb, grey(grey_index)) < distance(rgb, cube) {
+        (232 + grey_index) as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+fn grey(i: usize) -> (u8, u8, u8) {
+    let level = (8 + 10 * i) as u8;
+    (level, level, level)
+}
+
+/// The closest of the 16 ansi colors
+pub fn nearest_16(rgb: &ColorRGB) -> u8 {
+    (0..16).min_by_key(|i| distance(rgb, XTERM_COLORS[*i])).unwrap() as u8
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use vterm_sys::ColorRGB;
+
+    fn rgb(red: u8, green: u8, blue: u8) -> ColorRGB {
+        ColorRGB {
+            red: red,
+            green: green,
+            blue: blue,
+        }
+    }
+
+    #[test]
+    fn it_finds_the_nearest_cube_color() {
+        assert_eq!(nearest_256(&rgb(255, 0, 0)), 196);
+        assert_eq!(nearest_256(&rgb(90, 140, 210)), 16 + 36 + 6 * 2 + 4);
+    }
+
+    #[test]
+    fn it_finds_the_nearest_grey() {
+        assert_eq!(nearest_256(&rgb(128, 128, 130)), 244);
+    }
+
+    #[test]
+    fn it_finds_the_nearest_ansi_color() {
+        assert_eq!(nearest_16(&rgb(200, 10, 10)), 1);
+        assert_eq!(nearest_16(&rgb(250, 250, 240)), 15);
+    }
+}
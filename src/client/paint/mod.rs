@@ -1,5 +1,7 @@
+mod color;
 mod pen;
 mod tty_painter;
 
+pub use self::color::ColorSupport;
 pub use self::pen::Pen;
 pub use self::tty_painter::TtyPainter;
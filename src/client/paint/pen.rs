@@ -1,39 +1,48 @@
 use term::terminfo::{parm, TermInfo};
-use vterm_sys::{Size, Pos, ColorPalette};
-use ::cell_buffer::Cell;
+use vterm_sys::{Size, Pos, ColorPalette, ColorRGB};
+use ::cell_buffer::{Cell, is_palette_color, palette_rgb};
+use super::color::*;
+
+/// A color the way it is sent to the terminal
+#[derive(Clone, Debug, PartialEq)]
+enum Color {
+    Palette(ColorPalette),
+    Rgb(ColorRGB),
+}
 
 /// An object that tracks the state of the physical screen and collects desired changes to it. Can
 /// help make the desired changes reality by flushing them as a terminfo byte string.
 pub struct Pen {
     pub bg: ColorPalette,
+    /// set when the color isn't one of the palette's, and is used instead of bg
+    pub bg_rgb: Option<ColorRGB>,
     pub blink: bool,
     pub bold: bool,
+    /// Double height and double width are attributes of whole lines on the terminal, which would
+    /// stretch every pane on the line, so they aren't passed on
     pub dhl: u8,
     pub dwl: bool,
     pub fg: ColorPalette,
+    pub fg_rgb: Option<ColorRGB>,
     pub font: u8,
     pub visible: bool,
     pub italic: bool,
     pub pos: Pos,
     pub reverse: bool,
     pub strike: bool,
+    /// 0 for none, 1 for single, 2 for double and 3 for curly, like vterm
     pub underline: u8,
+    pub color_support: ColorSupport,
 
-    disp_bg: Option<ColorPalette>,
+    disp_bg: Option<Color>,
     disp_blink: Option<bool>,
     disp_bold: Option<bool>,
-    #[allow(dead_code)]
-    disp_dhl: Option<u8>,
-    #[allow(dead_code)]
-    disp_dwl: Option<bool>,
-    disp_fg: Option<ColorPalette>,
-    #[allow(dead_code)]
+    disp_fg: Option<Color>,
     disp_font: Option<u8>,
     disp_visible: Option<bool>,
     disp_italic: Option<bool>,
     disp_pos: Option<Pos>,
     disp_reverse: Option<bool>,
-    #[allow(dead_code)]
     disp_strike: Option<bool>,
     disp_underline: Option<u8>,
 }
@@ -42,11 +51,13 @@ impl Default for Pen {
     fn default() -> Pen {
         Pen {
             bg: 0,
+            bg_rgb: None,
             blink: false,
             bold: false,
             dhl: 0,
             dwl: false,
             fg: 7,
+            fg_rgb: None,
             font: 0,
             visible: true,
             italic: false,
@@ -54,11 +65,10 @@ impl Default for Pen {
             reverse: false,
             strike: false,
             underline: 0,
+            color_support: ColorSupport::Colors256,
             disp_bg: None,
             disp_blink: None,
             disp_bold: None,
-            disp_dhl: None,
-            disp_dwl: None,
             disp_fg: None,
             disp_font: None,
             disp_visible: None,
@@ -112,13 +122,42 @@ impl Pen {
         }
 
         if self.disp_underline.is_none() || *self.disp_underline.as_ref().unwrap() != self.underline {
+            // some terminals keep double underline apart from single, so the old style is taken
+            // off before the new one goes on
+            if self.underline == 0 || self.disp_underline.unwrap_or(0) != 0 {
+                self.apply_cap("rmul", &mut bytes, terminfo, vars, &vec![]);
+            }
             self.disp_underline = Some(self.underline);
 
-            if self.underline != 0 {
-                self.apply_cap("smul", &mut bytes, terminfo, vars, &vec![]);
+            match self.underline {
+                0 => {}
+                1 => self.apply_cap("smul", &mut bytes, terminfo, vars, &vec![]),
+                style if self.is_cap_supported("Smulx", terminfo) => {
+                    let params = vec![parm::Param::Number(style as i32)];
+                    self.apply_cap("Smulx", &mut bytes, terminfo, vars, &params);
+                }
+                // ECMA-48's double underline. There is no curly without Smulx, so it's single.
+                2 => bytes.extend(b"\x1b[21m"),
+                _ => self.apply_cap("smul", &mut bytes, terminfo, vars, &vec![]),
+            }
+        }
+
+        if self.disp_strike.is_none() || *self.disp_strike.as_ref().unwrap() != self.strike {
+            self.disp_strike = Some(self.strike);
+
+            if self.strike {
+                self.apply_cap_or("smxx", b"\x1b[9m", &mut bytes, terminfo, vars);
             } else {
-                self.apply_cap("rmul", &mut bytes, terminfo, vars, &vec![]);
-            };
+                self.apply_cap_or("rmxx", b"\x1b[29m", &mut bytes, terminfo, vars);
+            }
+        }
+
+        if self.disp_font.is_none() || *self.disp_font.as_ref().unwrap() != self.font {
+            self.disp_font = Some(self.font);
+
+            // SGR 10 is the primary font and 11 to 19 are the alternatives. Terminals without
+            // them ignore it.
+            bytes.extend(format!("\x1b[{}m", 10 + self.font as u32).as_bytes());
         }
 
         if self.is_cap_supported("sitm", terminfo) && self.is_cap_supported("ritm", terminfo) {
@@ -149,18 +188,16 @@ impl Pen {
             }
         }
 
-        if self.disp_fg.is_none() || *self.disp_fg.as_ref().unwrap() != self.fg {
-            self.disp_fg = Some(self.fg);
-
-            let params = vec![ parm::Param::Number(self.fg as i32) ];
-            self.apply_cap("setaf", &mut bytes, terminfo, vars, &params);
+        let fg = self.color(self.fg, &self.fg_rgb);
+        if self.disp_fg.as_ref() != Some(&fg) {
+            self.apply_color(&fg, "setaf", 38, &mut bytes, terminfo, vars);
+            self.disp_fg = Some(fg);
         }
 
-        if self.disp_bg.is_none() || *self.disp_bg.as_ref().unwrap() != self.bg {
-            self.disp_bg = Some(self.bg);
-
-            let params = vec![ parm::Param::Number(self.bg as i32) ];
-            self.apply_cap("setab", &mut bytes, terminfo, vars, &params);
+        let bg = self.color(self.bg, &self.bg_rgb);
+        if self.disp_bg.as_ref() != Some(&bg) {
+            self.apply_color(&bg, "setab", 48, &mut bytes, terminfo, vars);
+            self.disp_bg = Some(bg);
         }
 
         if (self.disp_visible.is_none() || !*self.disp_visible.as_ref().unwrap()) && self.visible {
@@ -176,15 +213,31 @@ impl Pen {
         self.disp_bold = Some(false);
         self.disp_reverse = Some(false);
         self.disp_underline = Some(0);
+        self.disp_italic = Some(false);
+        self.disp_strike = Some(false);
+        self.disp_font = Some(0);
+        // colors go back to the terminal's defaults
+        self.disp_fg = None;
+        self.disp_bg = None;
         self.apply_cap("sgr0", bytes, terminfo, vars, &vec![]);
     }
 
     /// Updates many pen attributes from the given cell's attributes
     pub fn update_attrs_from_cell(&mut self, cell: &Cell) {
         self.bg = cell.bg_palette;
+        self.bg_rgb = if is_palette_color(cell.bg_palette, &cell.bg_rgb) {
+            None
+        } else {
+            Some(cell.bg_rgb.clone())
+        };
         self.blink = cell.blink;
         self.bold = cell.bold;
         self.fg = cell.fg_palette;
+        self.fg_rgb = if is_palette_color(cell.fg_palette, &cell.fg_rgb) {
+            None
+        } else {
+            Some(cell.fg_rgb.clone())
+        };
         self.font = cell.font;
         self.italic = cell.italic;
         self.reverse = cell.reverse;
@@ -192,10 +245,11 @@ impl Pen {
         self.underline = cell.underline;
     }
 
-    /// Let the pen know that the physical display advanced the cursor one position
-    pub fn notify_of_advanced_pos(&mut self, screen_size: &Size) {
-        if self.pos.x + 1 < screen_size.width {
-            self.pos.x += 1;
+    /// Let the pen know that the physical display advanced the cursor, by two columns for a wide
+    /// char and one for anything else
+    pub fn notify_of_advanced_pos(&mut self, screen_size: &Size, columns: usize) {
+        if self.pos.x + columns < screen_size.width {
+            self.pos.x += columns;
         } else if self.pos.y + 1 < screen_size.height {
             self.pos.x = 0;
             self.pos.y += 1;
@@ -207,6 +261,41 @@ impl Pen {
         }
     }
 
+    /// Direct colors are sent as they are if the terminal can show them, otherwise the nearest
+    /// palette color is used
+    fn color(&self, palette: ColorPalette, rgb: &Option<ColorRGB>) -> Color {
+        match (rgb.as_ref(), self.color_support) {
+            (Some(rgb), ColorSupport::TrueColor) => Color::Rgb(rgb.clone()),
+            (Some(rgb), ColorSupport::Colors256) => Color::Palette(nearest_256(rgb)),
+            (Some(rgb), ColorSupport::Colors16) => Color::Palette(nearest_16(rgb)),
+            (None, ColorSupport::Colors16) if palette >= 16 => {
+                Color::Palette(nearest_16(&palette_rgb(palette)))
+            }
+            (None, _) => Color::Palette(palette),
+        }
+    }
+
+    /// terminfo has no cap for direct colors, so their SGR is written out. sgr is 38 for the
+    /// foreground and 48 for the background.
+    fn apply_color(&self,
+                   color: &Color,
+                   cap: &str,
+                   sgr: u8,
+                   bytes: &mut Vec<u8>,
+                   terminfo: &TermInfo,
+                   vars: &mut parm::Variables) {
+        match *color {
+            Color::Palette(index) => {
+                let params = vec![parm::Param::Number(index as i32)];
+                self.apply_cap(cap, bytes, terminfo, vars, &params);
+            }
+            Color::Rgb(ref rgb) => {
+                let sequence = format!("\x1b[{};2;{};{};{}m", sgr, rgb.red, rgb.green, rgb.blue);
+                bytes.extend(sequence.as_bytes());
+            }
+        }
+    }
+
     /// appends the cap sequence to the given bytes vec
     fn apply_cap(&self, cap: &str, bytes: &mut Vec<u8>, terminfo: &TermInfo, vars: &mut parm::Variables, params: &Vec<parm::Param>) {
         let cmd = terminfo.strings.get(cap).unwrap();
@@ -214,6 +303,20 @@ impl Pen {
         bytes.append(&mut sequence);
     }
 
+    /// Uses the terminfo cap if there is one, otherwise the sequence most terminals understand
+    fn apply_cap_or(&self,
+                    cap: &str,
+                    fallback: &[u8],
+                    bytes: &mut Vec<u8>,
+                    terminfo: &TermInfo,
+                    vars: &mut parm::Variables) {
+        if self.is_cap_supported(cap, terminfo) {
+            self.apply_cap(cap, bytes, terminfo, vars, &vec![]);
+        } else {
+            bytes.extend(fallback);
+        }
+    }
+
     fn is_cap_supported(&self, cap: &str, terminfo: &TermInfo) -> bool {
         terminfo.strings.get(cap).is_some()
     }
@@ -1,7 +1,10 @@
+use std::env;
 use std::io::prelude::*;
 use std::io::BufWriter;
+use rustc_serialize::base64::{ToBase64, STANDARD};
 use term::terminfo::{parm, TermInfo};
 use vterm_sys::{Size, Pos};
+use super::color::ColorSupport;
 use super::pen::*;
 use ::cell_buffer::*;
 
@@ -15,29 +18,49 @@ pub struct TtyPainter<F: Write + Send> {
     size: Size,
     terminfo: TermInfo,
     vars: parm::Variables,
+    color_support: ColorSupport,
 }
 
 impl<F: Write + Send> TtyPainter<F> {
     pub fn new(io: F, size: Size) -> TtyPainter<F> {
-        TtyPainter {
+        // Note: a better idea would be to have the caller choose the terminal type
+        let terminfo = TermInfo::from_env().unwrap();
+        let colorterm = env::var("COLORTERM").ok();
+        let color_support = ColorSupport::detect(&terminfo, colorterm.as_ref().map(|s| &s[..]));
+        info!("painting with {:?}", color_support);
+
+        let mut painter = TtyPainter {
             io: BufWriter::new(io),
             pen: Pen::new(),
             size: size,
-            // Note: a better idea would be to have the caller choose the terminal type
-            terminfo: TermInfo::from_env().unwrap(),
+            terminfo: terminfo,
             vars: parm::Variables::new(),
-        }
+            color_support: color_support,
+        };
+        painter.reset();
+        painter
     }
 
     pub fn draw_screen(&mut self, screen: &mut CellBuffer) {
         trace!("draw_screen start");
 
+        let old_pos = self.pen.pos.clone();
         let old_visible = self.pen.visible;
         self.pen.visible = false;
         let bytes = self.pen.flush(&self.terminfo, &mut self.vars);
         self.io.write_all(&bytes).ok().expect("failed to write");
 
-        self.write_cap("sc", &vec![]);
+        // A wide char is drawn from its left cell, so that has to be drawn again if only its right
+        // half changed
+        screen.mark_continuations();
+        let lefts: Vec<Pos> = screen.iter()
+                                    .filter(|p| p.0.dirty && p.0.is_continuation() && p.1.x > 0)
+                                    .map(|p| Pos::new(p.1.x - 1, p.1.y))
+                                    .collect();
+        for pos in lefts {
+            screen[pos].dirty = true;
+        }
+
         for pair in screen.iter_mut().filter(|p| p.0.dirty) {
             let mut cell = pair.0;
             let pos = pair.1;
@@ -50,29 +73,33 @@ impl<F: Write + Send> TtyPainter<F> {
                 continue;
             }
 
+            if cell.is_continuation() {
+                // drawn along with the wide char to its left
+                continue;
+            }
+
+            // a wide char in the last column would wrap, so a space is drawn instead
+            let is_wide = cell.width == 2 && pos.x + 1 < self.size.width;
+
             self.pen.pos = pos.clone();
             self.pen.update_attrs_from_cell(cell);
             let bytes = self.pen.flush(&self.terminfo, &mut self.vars);
             self.io.write_all(&bytes).ok().expect("failed to write");
 
             // See tmux's tty.c:1155 function `tty_cell`
-            if cell.chars.len() > 0 {
+            if cell.chars.len() > 0 && (is_wide || cell.width != 2) {
                 self.io.write_all(&cell.chars).ok().expect("failed to write");
             } else {
                 // like tmux's tty_repeat_space
                 self.io.write_all(&[b'\x20']).ok().expect("failed to write"); // space
             }
 
-            self.pen.notify_of_advanced_pos(&self.size);
-
-            if cell.width > 1 {
-                warn!("cell has width > 1 {:?}, but acting on this information isnt implemented",
-                      cell)
-            }
+            self.pen.notify_of_advanced_pos(&self.size, if is_wide { 2 } else { 1 });
         }
 
-        self.write_cap("rc", &vec![]);
-
+        // the cursor goes back through the pen rather than with `rc`, so the pen still knows where
+        // it is and which attributes are set
+        self.pen.pos = old_pos;
         self.pen.visible = old_visible;
         let bytes = self.pen.flush(&self.terminfo, &mut self.vars);
         self.io.write_all(&bytes).ok().expect("failed to write");
@@ -86,6 +113,16 @@ impl<F: Write + Send> TtyPainter<F> {
         self.pen.pos = pos;
         self.pen.visible = is_visible;
         self.io.write_all(&self.pen.flush(&self.terminfo, &mut self.vars)).ok().expect("failed to write");
+        self.io.flush().unwrap();
+    }
+
+    /// Hides the cursor where it is, for when no program's cursor should be shown
+    pub fn hide_cursor(&mut self) {
+        trace!("hide_cursor");
+        self.pen.visible = false;
+        let bytes = self.pen.flush(&self.terminfo, &mut self.vars);
+        self.io.write_all(&bytes).ok().expect("failed to write");
+        self.io.flush().unwrap();
     }
 
     /// Implemented like tmux's tty_redraw_region
@@ -107,6 +144,74 @@ impl<F: Write + Send> TtyPainter<F> {
 
     pub fn reset(&mut self) {
         self.pen = Pen::new();
+        self.pen.color_support = self.color_support;
+    }
+
+    /// The terminal has changed size, and what's on it can't be trusted anymore. It's cleared so
+    /// the next draw_screen starts from a blank slate.
+    pub fn resize(&mut self, size: Size) {
+        self.size = size;
+        self.write_cap("sgr0", &vec![]);
+        self.write_cap("clear", &vec![]);
+        self.reset();
+        self.flush();
+    }
+
+    /// Sets the terminal's clipboard with OSC 52. Terminals that don't support it ignore it.
+    pub fn set_clipboard(&mut self, bytes: &[u8]) {
+        let osc = format!("\x1b]52;c;{}\x07", bytes.to_base64(STANDARD));
+        self.io.write_all(osc.as_bytes()).ok().expect("failed to write");
+        self.io.flush().unwrap();
+    }
+
+    /// Turns the terminal's reporting of presses, releases and drags on or off. Reports come as
+    /// `ESC [ < b ; x ; y M`.
+    pub fn set_mouse_reporting(&mut self, is_enabled: bool) {
+        let bytes: &[u8] = if is_enabled {
+            b"\x1b[?1002h\x1b[?1006h"
+        } else {
+            b"\x1b[?1006l\x1b[?1002l"
+        };
+        self.io.write_all(bytes).ok().expect("failed to write");
+        self.io.flush().unwrap();
+    }
+
+    /// Sets the cursor's shape and whether it blinks, with DECSCUSR's numbering. 0 goes back to
+    /// the user's default. Terminfo's Ss and Se are used if the terminal has them.
+    pub fn set_cursor_style(&mut self, style: u8) {
+        if style == 0 && self.terminfo.strings.contains_key("Se") {
+            self.write_cap("Se", &vec![]);
+        } else if self.terminfo.strings.contains_key("Ss") {
+            self.write_cap("Ss", &vec![parm::Param::Number(style as i32)]);
+        } else {
+            let decscusr = format!("\x1b[{} q", style);
+            self.io.write_all(decscusr.as_bytes()).ok().expect("failed to write");
+        }
+        self.io.flush().unwrap();
+    }
+
+    pub fn bell(&mut self) {
+        self.io.write_all(b"\x07").ok().expect("failed to write");
+        self.io.flush().unwrap();
+    }
+
+    /// Sets the terminal's window title with OSC 2
+    pub fn set_title(&mut self, title: &str) {
+        let osc = format!("\x1b]2;{}\x07", title);
+        self.io.write_all(osc.as_bytes()).ok().expect("failed to write");
+        self.io.flush().unwrap();
+    }
+
+    /// Has the terminal push its title onto its title stack, for restore_title to put back. Not
+    /// every terminal has one, but those that don't ignore it.
+    pub fn save_title(&mut self) {
+        self.io.write_all(b"\x1b[22;0t").ok().expect("failed to write");
+        self.io.flush().unwrap();
+    }
+
+    pub fn restore_title(&mut self) {
+        self.io.write_all(b"\x1b[23;0t").ok().expect("failed to write");
+        self.io.flush().unwrap();
     }
 
     // pub fn delete_line<F: Write>(&mut self, pane: &Pane, io: &mut F) {
@@ -1,6 +1,7 @@
-use std::sync::mpsc::*;
 use std::fmt;
 use std::slice;
+use transport::Tx;
+use vterm_sys::Pos;
 
 #[derive(Default, Clone, Debug)]
 pub struct Servers {
@@ -20,10 +21,48 @@ impl Servers {
         self.servers.first()
     }
 
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Server> {
+        self.servers.iter().find(|s| s.id == id)
+    }
+
+    /// Find the server and program shown in the wrap with the given name
+    pub fn find_by_wrap_name(&self, wrap_name: &str) -> Option<(&Server, &Program)> {
+        for server in self.servers.iter() {
+            if let Some(program) = server.programs
+                                         .iter()
+                                         .find(|p| wrap_name_for(&server.id, &p.id) == wrap_name) {
+                return Some((server, program));
+            }
+        }
+        None
+    }
+
     pub fn add_server(&mut self, server: Server) {
-        if !self.servers.iter().any(|w| w.id == server.id) {
-            self.servers.push(server)
+        if self.servers.iter().any(|w| w.id == server.id) {
+            warn!("already have a server called {:?}, ignoring the new one", server.id);
+            return;
+        }
+        self.servers.push(server)
+    }
+
+    /// Swap in a new connection to a server we already know, for example after reconnecting. Its
+    /// programs are kept, but need subscribing to again.
+    pub fn update_server(&mut self, server: Server) {
+        if let Some(existing) = self.servers.iter_mut().find(|w| w.id == server.id) {
+            existing.client_id = server.client_id;
+            existing.tx = server.tx;
+            existing.is_connected = server.is_connected;
+            for program in existing.programs.iter_mut() {
+                program.is_subscribed = false;
+            }
+            return;
         }
+
+        self.servers.push(server)
     }
 
     pub fn remove_server(&mut self, id: &str) {
@@ -32,6 +71,30 @@ impl Servers {
         }
     }
 
+    pub fn find_program_mut(&mut self, server_id: &str, program_id: &str) -> Option<&mut Program> {
+        self.servers
+            .iter_mut()
+            .find(|s| s.id == server_id)
+            .and_then(|s| s.programs.iter_mut().find(|p| p.id == program_id))
+    }
+
+    pub fn remove_program(&mut self, server_id: &str, program_id: &str) {
+        if let Some(server) = self.servers.iter_mut().find(|s| s.id == server_id) {
+            server.programs.retain(|p| p.id != program_id);
+        }
+    }
+
+    /// Keep the server and its programs around so their panes can still be shown, but stop
+    /// talking to it.
+    pub fn disconnect_server(&mut self, id: &str) {
+        if let Some(server) = self.servers.iter_mut().find(|w| w.id == id) {
+            server.is_connected = false;
+            for program in server.programs.iter_mut() {
+                program.is_subscribed = false;
+            }
+        }
+    }
+
     pub fn add_program(&mut self, server_id: &str, program: Program) {
         let server = self.servers.iter_mut().find(|w| w.id == server_id);
         if server.is_none() {
@@ -52,17 +115,34 @@ impl Servers {
 #[derive(Clone)]
 pub struct Server {
     pub id: String,
+    /// The id the server gave this client. Msgs sent over a socket have it stamped on by the
+    /// server, so it only matters for servers in the same process, reached over a channel.
+    pub client_id: String,
     pub programs: Vec<Program>,
+    pub tx: Tx<::server::ServerMsg>,
+    /// False once the connection has gone away
+    pub is_connected: bool,
+}
 
-    /// replace with with cap'n proto or whatever
-    pub tx: Sender<::server::ServerMsg>,
+impl Server {
+    pub fn new(id: &str, client_id: &str, tx: Tx<::server::ServerMsg>) -> Server {
+        Server {
+            id: id.to_string(),
+            client_id: client_id.to_string(),
+            programs: vec![],
+            tx: tx,
+            is_connected: true,
+        }
+    }
 }
 
 impl fmt::Debug for Server {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("Server")
            .field("id", &self.id)
+           .field("client_id", &self.client_id)
            .field("programs", &self.programs)
+           .field("is_connected", &self.is_connected)
            .finish()
     }
 }
@@ -74,4 +154,54 @@ pub struct Program {
     /// Whether the client is interested in msgs about this program. If its not visible, the answer
     /// is probably no.
     pub is_subscribed: bool,
+    /// Set once the program has exited, to its exit code or 128 plus the signal that killed it
+    pub exit_status: Option<u8>,
+    /// What the program set its title and icon name to, empty if it hasn't
+    pub title: String,
+    pub icon_name: String,
+    /// Set when the program rang the bell, started writing again after being quiet, or went
+    /// quiet, while it wasn't selected. They're cleared when it is selected.
+    pub has_bell: bool,
+    pub has_activity: bool,
+    pub has_silence: bool,
+    /// Whether the user asked to be told when the program goes quiet
+    pub monitor_silence: bool,
+    /// The cursor style the program set, with DECSCUSR's numbering
+    pub cursor_style: u8,
+    /// Where the program's cursor is in its pane, once it has said, and whether it hid it. Only
+    /// the selected program's cursor is shown.
+    pub cursor_pos: Option<Pos>,
+    pub is_cursor_hidden: bool,
+}
+
+impl Program {
+    /// The title, or the icon name for programs that only set that
+    pub fn display_title(&self) -> &str {
+        if self.title.is_empty() {
+            &self.icon_name
+        } else {
+            &self.title
+        }
+    }
+
+    /// `!` for the bell, `#` for activity and `~` for silence
+    pub fn flags(&self) -> String {
+        let mut flags = String::new();
+        if self.has_bell {
+            flags.push('!');
+        }
+        if self.has_activity {
+            flags.push('#');
+        }
+        if self.has_silence {
+            flags.push('~');
+        }
+        flags
+    }
+}
+
+/// Programs from every server share the client's layout, and program ids are only unique on
+/// their own server, so each program's wrap is named after both.
+pub fn wrap_name_for(server_id: &str, program_id: &str) -> String {
+    format!("{}/{}", server_id, program_id)
 }
@@ -0,0 +1,117 @@
+use libc::{self, c_int, c_ushort};
+use std::io;
+use std::os::unix::prelude::*;
+use std::sync::mpsc::*;
+use std::thread::{self, JoinHandle};
+use super::*;
+use vterm_sys::Size;
+
+// The signal handler can't do much safely, so it writes a byte to this pipe and the worker thread
+// does the rest.
+static mut WINCH_PIPE_WRITE_FD: c_int = -1;
+
+extern "C" fn handle_sigwinch(_: c_int) {
+    unsafe {
+        let byte = 0u8;
+        libc::write(WINCH_PIPE_WRITE_FD, &byte as *const u8 as *const libc::c_void, 1);
+    }
+}
+
+/// Sends a TtyResize msg whenever the terminal is resized, which the kernel signals with SIGWINCH.
+pub struct WinchWorker {
+    client_tx: Sender<ClientMsg>,
+    tty_fd: RawFd,
+    pipe_read_fd: RawFd,
+}
+
+impl WinchWorker {
+    /// Installs the SIGWINCH handler, so only spawn one of these per process.
+    pub fn spawn(tty_fd: RawFd, tx: Sender<ClientMsg>) -> io::Result<JoinHandle<()>> {
+        let mut fds = [0 as c_int; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        unsafe {
+            WINCH_PIPE_WRITE_FD = fds[1];
+            let handler = handle_sigwinch as extern "C" fn(c_int);
+            if libc::signal(libc::SIGWINCH, handler as libc::sighandler_t) == libc::SIG_ERR {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        info!("spawning winch worker thread");
+        let handle = thread::spawn(move || {
+            let mut worker = WinchWorker {
+                client_tx: tx,
+                tty_fd: tty_fd,
+                pipe_read_fd: fds[0],
+            };
+            worker.enter_read_loop();
+            info!("exiting winch worker thread");
+        });
+
+        Ok(handle)
+    }
+
+    /// Exits when the client goes away.
+    fn enter_read_loop(&mut self) {
+        let mut buf = [0u8; 64];
+        loop {
+            let num_bytes = unsafe {
+                libc::read(self.pipe_read_fd,
+                           buf.as_mut_ptr() as *mut libc::c_void,
+                           buf.len())
+            };
+            if num_bytes < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                error!("reading winch pipe failed: {}", e);
+                break;
+            }
+
+            // several signals in a row only need one resize
+            let size = match tty_size(self.tty_fd) {
+                Ok(size) => size,
+                Err(e) => {
+                    error!("couldnt get tty size: {}", e);
+                    continue;
+                }
+            };
+            if self.client_tx.send(ClientMsg::TtyResize { size: size }).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+struct WinSize {
+    rows: c_ushort,
+    cols: c_ushort,
+    x_pixels: c_ushort,
+    y_pixels: c_ushort,
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+const TIOCGWINSZ: libc::c_ulong = 0x40087468;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TIOCGWINSZ: libc::c_ulong = 0x5413;
+
+/// The size of the terminal, in cells
+pub fn tty_size(fd: RawFd) -> io::Result<Size> {
+    let mut win_size = WinSize {
+        rows: 0,
+        cols: 0,
+        x_pixels: 0,
+        y_pixels: 0,
+    };
+
+    if unsafe { libc::ioctl(fd, TIOCGWINSZ, &mut win_size) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Size::new(win_size.cols as usize, win_size.rows as usize))
+}
@@ -7,6 +7,7 @@ extern crate ego_tree;
 extern crate itertools;
 extern crate libc;
 extern crate pty;
+extern crate regex;
 extern crate rustc_serialize;
 extern crate term;
 extern crate termios;
@@ -15,4 +16,6 @@ extern crate vterm_sys;
 
 pub mod cell_buffer;
 pub mod client;
+pub mod protocol;
 pub mod server;
+pub mod transport;
@@ -0,0 +1,596 @@
+//! The wire protocol spoken between clients and servers.
+//!
+//! See doc/protocol.md for a description aimed at people writing their own clients.
+
+mod msgs;
+mod vterm;
+
+use std::cmp;
+use std::io::prelude::*;
+use std::io;
+
+/// Bump this whenever the encoding of any msg changes, including adding msgs. Peers with
+/// different versions refuse to talk to each other.
+pub const PROTOCOL_VERSION: u32 = 24;
+
+/// Sent at the start of every hello so a peer can tell it is talking to intermix at all.
+pub const MAGIC: &'static str = "intermix";
+
+/// Frames claiming to be bigger than this are assumed to be garbage rather than allocated.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Arrays are read into vecs that start with room for at most this many items, since the length
+/// comes off the wire and each item can be much bigger than the byte it takes up there.
+const MAX_PREALLOC_ITEMS: usize = 1024;
+
+/// Something that can be written to and read back from a byte stream as msgpack, so it can be
+/// sent between the client and server processes.
+pub trait Wire: Sized {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()>;
+    fn decode<R: Read>(r: &mut R) -> io::Result<Self>;
+}
+
+/// The first frame each side sends after connecting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub version: u32,
+    /// The id the sender goes by. Servers give each client connection an id of their own, so a
+    /// client's only shows up in the server's logs.
+    pub id: String,
+}
+
+impl Hello {
+    pub fn new(id: &str) -> Hello {
+        Hello {
+            version: PROTOCOL_VERSION,
+            id: id.to_string(),
+        }
+    }
+}
+
+impl Wire for Hello {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_array_len(w, 3));
+        try!(MAGIC.to_string().encode(w));
+        try!(self.version.encode(w));
+        self.id.encode(w)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<Hello> {
+        // check the magic before the length, so old or foreign peers get a useful error
+        let len = try!(read_array_len(r));
+        if len == 0 || try!(String::decode(r)) != MAGIC {
+            return Err(invalid_data("peer isn't speaking the intermix protocol"));
+        }
+
+        let version = try!(u32::decode(r));
+        if version != PROTOCOL_VERSION {
+            return Ok(Hello {
+                version: version,
+                id: String::new(),
+            });
+        }
+
+        if len != 3 {
+            return Err(invalid_data(&format!("expected Hello to have 3 fields but it has {}",
+                                             len)));
+        }
+        Ok(Hello {
+            version: version,
+            id: try!(Wire::decode(r)),
+        })
+    }
+}
+
+/// Exchange hellos with the peer on the other end of the stream. Both sides send theirs before
+/// reading, so neither waits on the other. Fails if the peer speaks a different version.
+pub fn handshake<S: Read + Write>(stream: &mut S, id: &str) -> io::Result<Hello> {
+    try!(write_msg(stream, &Hello::new(id)));
+
+    // Not read_msg, because a hello from another version may have fields we don't understand.
+    let frame = try!(read_frame(stream));
+    let theirs = try!(Hello::decode(&mut frame.as_slice()));
+
+    if theirs.version != PROTOCOL_VERSION {
+        return Err(io::Error::new(io::ErrorKind::Other,
+                                  format!("peer speaks protocol version {}, but we speak {}",
+                                          theirs.version,
+                                          PROTOCOL_VERSION)));
+    }
+
+    Ok(theirs)
+}
+
+/// Write the msg as a frame, which is the byte length of the encoded msg as a big endian u32
+/// followed by the msg itself.
+pub fn write_msg<W: Write, T: Wire>(w: &mut W, msg: &T) -> io::Result<()> {
+    let mut body: Vec<u8> = vec![];
+    try!(msg.encode(&mut body));
+
+    let len = body.len() as u32;
+    try!(w.write_all(&[(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8]));
+    try!(w.write_all(&body));
+    w.flush()
+}
+
+/// Read one frame and return the bytes inside it. Blocks until a full frame is available.
+pub fn read_frame<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let mut len_buf = [0u8; 4];
+    try!(r.read_exact(&mut len_buf));
+    let len = len_buf.iter().fold(0, |acc, b| acc << 8 | *b as usize);
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(&format!("frame length {} is too big", len)));
+    }
+
+    let mut body = vec![0u8; len];
+    try!(r.read_exact(&mut body));
+    Ok(body)
+}
+
+/// Read one frame and decode the msg inside it. Blocks until a full frame is available.
+pub fn read_msg<R: Read, T: Wire>(r: &mut R) -> io::Result<T> {
+    let body = try!(read_frame(r));
+
+    let mut body = body.as_slice();
+    let msg = try!(T::decode(&mut body));
+    if body.len() > 0 {
+        return Err(invalid_data(&format!("{} unread bytes left in frame", body.len())));
+    }
+    Ok(msg)
+}
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_marker<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    try!(r.read_exact(&mut buf));
+    Ok(buf[0])
+}
+
+fn read_be<R: Read>(r: &mut R, num_bytes: usize) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    try!(r.read_exact(&mut buf[0..num_bytes]));
+    Ok(buf[0..num_bytes].iter().fold(0, |acc, b| acc << 8 | *b as u64))
+}
+
+fn write_be<W: Write>(w: &mut W, marker: u8, val: u64, num_bytes: usize) -> io::Result<()> {
+    let mut bytes = vec![marker];
+    for i in (0..num_bytes).rev() {
+        bytes.push((val >> (i * 8)) as u8);
+    }
+    w.write_all(&bytes)
+}
+
+/// Write an unsigned int using the smallest msgpack format that fits it
+pub fn write_uint<W: Write>(w: &mut W, val: u64) -> io::Result<()> {
+    if val < 0x80 {
+        w.write_all(&[val as u8])
+    } else if val <= 0xff {
+        write_be(w, 0xcc, val, 1)
+    } else if val <= 0xffff {
+        write_be(w, 0xcd, val, 2)
+    } else if val <= 0xffff_ffff {
+        write_be(w, 0xce, val, 4)
+    } else {
+        write_be(w, 0xcf, val, 8)
+    }
+}
+
+/// Read an unsigned int in any of the msgpack uint formats
+pub fn read_uint<R: Read>(r: &mut R) -> io::Result<u64> {
+    match try!(read_marker(r)) {
+        m @ 0x00...0x7f => Ok(m as u64),
+        0xcc => read_be(r, 1),
+        0xcd => read_be(r, 2),
+        0xce => read_be(r, 4),
+        0xcf => read_be(r, 8),
+        m => Err(invalid_data(&format!("expected uint but got marker {:#x}", m))),
+    }
+}
+
+fn read_uint_max<R: Read>(r: &mut R, max: u64) -> io::Result<u64> {
+    let val = try!(read_uint(r));
+    if val > max {
+        return Err(invalid_data(&format!("{} is out of range", val)));
+    }
+    Ok(val)
+}
+
+pub fn write_array_len<W: Write>(w: &mut W, len: usize) -> io::Result<()> {
+    if len < 16 {
+        w.write_all(&[0x90 | len as u8])
+    } else if len <= 0xffff {
+        write_be(w, 0xdc, len as u64, 2)
+    } else {
+        write_be(w, 0xdd, len as u64, 4)
+    }
+}
+
+pub fn read_array_len<R: Read>(r: &mut R) -> io::Result<usize> {
+    let len = match try!(read_marker(r)) {
+        m @ 0x90...0x9f => (m & 0x0f) as usize,
+        0xdc => try!(read_be(r, 2)) as usize,
+        0xdd => try!(read_be(r, 4)) as usize,
+        m => return Err(invalid_data(&format!("expected array but got marker {:#x}", m))),
+    };
+
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(&format!("array length {} is too big", len)));
+    }
+    Ok(len)
+}
+
+/// Read an array header and fail unless it has the expected number of items
+pub fn expect_array_len<R: Read>(r: &mut R, expected: usize, name: &str) -> io::Result<()> {
+    let len = try!(read_array_len(r));
+    if len != expected {
+        return Err(invalid_data(&format!("expected {} to have {} fields but it has {}",
+                                         name,
+                                         expected,
+                                         len)));
+    }
+    Ok(())
+}
+
+/// Write bytes as msgpack bin. Vec<u8> would otherwise be written as an array of uints.
+pub fn write_bin<W: Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let len = bytes.len() as u64;
+    if len <= 0xff {
+        try!(write_be(w, 0xc4, len, 1));
+    } else if len <= 0xffff {
+        try!(write_be(w, 0xc5, len, 2));
+    } else {
+        try!(write_be(w, 0xc6, len, 4));
+    }
+    w.write_all(bytes)
+}
+
+pub fn read_bin<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let len = match try!(read_marker(r)) {
+        0xc4 => try!(read_be(r, 1)) as usize,
+        0xc5 => try!(read_be(r, 2)) as usize,
+        0xc6 => try!(read_be(r, 4)) as usize,
+        m => return Err(invalid_data(&format!("expected bin but got marker {:#x}", m))),
+    };
+
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(&format!("bin length {} is too big", len)));
+    }
+
+    let mut bytes = vec![0u8; len];
+    try!(r.read_exact(&mut bytes));
+    Ok(bytes)
+}
+
+impl Wire for u8 {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_uint(w, *self as u64)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<u8> {
+        Ok(try!(read_uint_max(r, u8::max_value() as u64)) as u8)
+    }
+}
+
+impl Wire for u16 {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_uint(w, *self as u64)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<u16> {
+        Ok(try!(read_uint_max(r, u16::max_value() as u64)) as u16)
+    }
+}
+
+impl Wire for u32 {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_uint(w, *self as u64)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<u32> {
+        Ok(try!(read_uint_max(r, u32::max_value() as u64)) as u32)
+    }
+}
+
+impl Wire for u64 {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_uint(w, *self)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<u64> {
+        read_uint(r)
+    }
+}
+
+impl Wire for usize {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_uint(w, *self as u64)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<usize> {
+        Ok(try!(read_uint_max(r, usize::max_value() as u64)) as usize)
+    }
+}
+
+impl Wire for bool {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&[if *self { 0xc3 } else { 0xc2 }])
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<bool> {
+        match try!(read_marker(r)) {
+            0xc2 => Ok(false),
+            0xc3 => Ok(true),
+            m => Err(invalid_data(&format!("expected bool but got marker {:#x}", m))),
+        }
+    }
+}
+
+impl Wire for String {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let len = self.len() as u64;
+        if len < 32 {
+            try!(w.write_all(&[0xa0 | len as u8]));
+        } else if len <= 0xff {
+            try!(write_be(w, 0xd9, len, 1));
+        } else if len <= 0xffff {
+            try!(write_be(w, 0xda, len, 2));
+        } else {
+            try!(write_be(w, 0xdb, len, 4));
+        }
+        w.write_all(self.as_bytes())
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<String> {
+        let len = match try!(read_marker(r)) {
+            m @ 0xa0...0xbf => (m & 0x1f) as usize,
+            0xd9 => try!(read_be(r, 1)) as usize,
+            0xda => try!(read_be(r, 2)) as usize,
+            0xdb => try!(read_be(r, 4)) as usize,
+            m => return Err(invalid_data(&format!("expected str but got marker {:#x}", m))),
+        };
+
+        if len > MAX_FRAME_LEN {
+            return Err(invalid_data(&format!("str length {} is too big", len)));
+        }
+
+        let mut buf = vec![0u8; len];
+        try!(r.read_exact(&mut buf));
+        String::from_utf8(buf).map_err(|_| invalid_data("str isn't utf8"))
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_array_len(w, self.len()));
+        for item in self {
+            try!(item.encode(w));
+        }
+        Ok(())
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<Vec<T>> {
+        let len = try!(read_array_len(r));
+
+        let mut items = Vec::with_capacity(cmp::min(len, MAX_PREALLOC_ITEMS));
+        for _ in 0..len {
+            items.push(try!(T::decode(r)));
+        }
+        Ok(items)
+    }
+}
+
+/// None is msgpack nil, Some is the value itself.
+impl<T: Wire> Wire for Option<T> {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match *self {
+            Some(ref val) => val.encode(w),
+            None => w.write_all(&[0xc0]),
+        }
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<Option<T>> {
+        let marker = try!(read_marker(r));
+        if marker == 0xc0 {
+            return Ok(None);
+        }
+
+        // put the marker back in front of the rest
+        let marker_buf = [marker];
+        let mut chained = (&marker_buf[..]).chain(r);
+        Ok(Some(try!(T::decode(&mut chained))))
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use std::io::prelude::*;
+    use vterm_sys::{ScreenCell, Rect, Pos, Size};
+    use server::ServerMsg;
+    use client::ClientMsg;
+
+    #[allow(dead_code)]
+    fn round_trip<T: Wire>(val: &T) -> T {
+        let mut bytes: Vec<u8> = vec![];
+        write_msg(&mut bytes, val).unwrap();
+        read_msg(&mut bytes.as_slice()).unwrap()
+    }
+
+    #[allow(dead_code)]
+    fn encoded<T: Wire>(val: &T) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![];
+        val.encode(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn it_round_trips_numbers() {
+        assert_eq!(round_trip(&7u8), 7u8);
+        assert_eq!(round_trip(&300u16), 300u16);
+        assert_eq!(round_trip(&70000u32), 70000u32);
+        assert_eq!(round_trip(&(u32::max_value() as u64 + 1)), u32::max_value() as u64 + 1);
+        assert_eq!(round_trip(&12usize), 12usize);
+    }
+
+    #[test]
+    fn it_round_trips_strings_and_vecs() {
+        assert_eq!(round_trip(&"hi there".to_string()), "hi there".to_string());
+        assert_eq!(round_trip(&vec![1u8, 2, 3]), vec![1u8, 2, 3]);
+        assert_eq!(round_trip(&Some(true)), Some(true));
+        assert_eq!(round_trip::<Option<bool>>(&None), None);
+    }
+
+    #[test]
+    fn it_encodes_as_msgpack() {
+        assert_eq!(encoded(&5u8), vec![0x05]);
+        assert_eq!(encoded(&300u16), vec![0xcd, 0x01, 0x2c]);
+        assert_eq!(encoded(&true), vec![0xc3]);
+        assert_eq!(encoded(&"hi".to_string()), vec![0xa2, b'h', b'i']);
+        assert_eq!(encoded(&vec![1u8, 2]), vec![0x92, 0x01, 0x02]);
+
+        let mut bytes: Vec<u8> = vec![];
+        write_bin(&mut bytes, b"hi").unwrap();
+        assert_eq!(bytes, vec![0xc4, 0x02, b'h', b'i']);
+    }
+
+    #[test]
+    fn it_refuses_out_of_range_numbers() {
+        let bytes = encoded(&300u16);
+        assert!(u8::decode(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn it_refuses_huge_frames() {
+        let bytes = vec![0xffu8, 0xff, 0xff, 0xff];
+        let result: ::std::io::Result<u8> = read_msg(&mut bytes.as_slice());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn it_doesnt_trust_array_lengths() {
+        // claims to hold 64M cells, but has none of them
+        let bytes = vec![0xddu8, 0x04, 0x00, 0x00, 0x00];
+        let result: ::std::io::Result<Vec<ScreenCell>> = Wire::decode(&mut bytes.as_slice());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn it_round_trips_program_damage_cells() {
+        let mut cell: ScreenCell = Default::default();
+        cell.chars = "é".to_string().into_bytes();
+        cell.width = 1;
+        cell.attrs.bold = true;
+        cell.attrs.underline = 2;
+        cell.fg_palette = 3;
+        cell.bg_rgb.red = 200;
+
+        let msg = ClientMsg::ProgramDamage {
+            server_id: "a server".to_string(),
+            program_id: "123".to_string(),
+            cells: vec![cell.clone(), Default::default()],
+            rect: Rect::new(Pos::new(1, 2), Size::new(2, 1)),
+        };
+
+        match round_trip(&msg) {
+            ClientMsg::ProgramDamage { server_id, program_id, cells, rect } => {
+                assert_eq!(server_id, "a server");
+                assert_eq!(program_id, "123");
+                assert_eq!(cells.len(), 2);
+                assert_eq!(cells[0].chars, cell.chars);
+                assert_eq!(cells[0].attrs.bold, true);
+                assert_eq!(cells[0].attrs.underline, 2);
+                assert_eq!(cells[0].fg_palette, 3);
+                assert_eq!(cells[0].bg_rgb.red, 200);
+                assert_eq!(rect, Rect::new(Pos::new(1, 2), Size::new(2, 1)));
+            }
+            _ => panic!("decoded the wrong variant"),
+        }
+    }
+
+    #[test]
+    fn it_refuses_to_send_internal_msgs() {
+        let msg = ServerMsg::ProgramMoveCursor {
+            program_id: "123".to_string(),
+            new: Pos::new(0, 0),
+            old: Pos::new(0, 0),
+            is_visible: true,
+        };
+        let mut bytes: Vec<u8> = vec![];
+        assert!(write_msg(&mut bytes, &msg).is_err());
+    }
+
+    #[test]
+    fn it_refuses_to_send_msgs_the_client_makes_for_itself() {
+        let mut bytes: Vec<u8> = vec![];
+        assert!(write_msg(&mut bytes, &ClientMsg::UserInput { bytes: b"ls\r".to_vec() })
+                    .is_err());
+        assert!(write_msg(&mut bytes, &ClientMsg::Clear).is_err());
+        assert!(write_msg(&mut bytes, &ClientMsg::LayoutDamage).is_err());
+        assert!(write_msg(&mut bytes, &ClientMsg::StatusLineDamage).is_err());
+        assert!(bytes.is_empty());
+    }
+
+    /// Reads from one buffer and writes to another, like a socket
+    #[allow(dead_code)]
+    struct Pipe {
+        input: ::std::io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[allow(dead_code)]
+    fn pipe_with_hello(hello: Hello) -> Pipe {
+        let mut input: Vec<u8> = vec![];
+        write_msg(&mut input, &hello).unwrap();
+        Pipe {
+            input: ::std::io::Cursor::new(input),
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn handshake_accepts_same_version() {
+        let mut pipe = pipe_with_hello(Hello::new("server"));
+        assert_eq!(handshake(&mut pipe, "client").unwrap(), Hello::new("server"));
+
+        let sent: Hello = read_msg(&mut pipe.output.as_slice()).unwrap();
+        assert_eq!(sent, Hello::new("client"));
+    }
+
+    #[test]
+    fn handshake_rejects_other_versions() {
+        let mut pipe = pipe_with_hello(Hello {
+            version: PROTOCOL_VERSION + 1,
+            id: "server".to_string(),
+        });
+        let err = handshake(&mut pipe, "client").unwrap_err();
+        assert!(format!("{}", err).contains("protocol version"));
+    }
+
+    #[test]
+    fn hello_refuses_non_intermix_peers() {
+        let mut bytes: Vec<u8> = vec![];
+        write_array_len(&mut bytes, 2).unwrap();
+        "tmux".to_string().encode(&mut bytes).unwrap();
+        1u32.encode(&mut bytes).unwrap();
+        assert!(Hello::decode(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,561 @@
+use std::io::prelude::*;
+use std::io;
+use super::*;
+use client::ClientMsg;
+use server::ServerMsg;
+
+// Each msg is a msgpack array whose first item is one of these tags, followed by the variant's
+// fields in declaration order. Changing them needs a new PROTOCOL_VERSION.
+
+const SERVER_QUIT: u8 = 0;
+const SERVER_PROGRAM_INPUT: u8 = 1;
+const SERVER_PROGRAM_KILL: u8 = 2;
+const SERVER_PROGRAM_REDRAW_RECT: u8 = 3;
+const SERVER_PROGRAM_START: u8 = 4;
+const SERVER_PROGRAM_SUBSCRIBE: u8 = 5;
+const SERVER_PROGRAM_UNSUBSCRIBE: u8 = 6;
+const SERVER_PROGRAM_RESIZE: u8 = 7;
+const SERVER_PROGRAM_REMOVE: u8 = 8;
+const SERVER_PROGRAM_RESTART: u8 = 9;
+const SERVER_PROGRAM_SCROLLBACK_FETCH: u8 = 10;
+const SERVER_BUFFER_SET: u8 = 11;
+const SERVER_BUFFER_PASTE: u8 = 12;
+const SERVER_PROGRAM_MOUSE: u8 = 13;
+const SERVER_PROGRAM_MONITOR_SILENCE: u8 = 14;
+
+const CLIENT_QUIT: u8 = 0;
+const CLIENT_SERVER_REMOVE: u8 = 1;
+const CLIENT_PROGRAM_ADD: u8 = 2;
+const CLIENT_PROGRAM_UPDATE: u8 = 3;
+const CLIENT_PROGRAM_REMOVE: u8 = 4;
+const CLIENT_PROGRAM_DAMAGE: u8 = 5;
+const CLIENT_PROGRAM_MOVE_CURSOR: u8 = 6;
+const CLIENT_PROGRAM_RESIZE: u8 = 7;
+const CLIENT_PROGRAM_EXITED: u8 = 8;
+const CLIENT_PROGRAM_SCROLLBACK: u8 = 9;
+const CLIENT_CLIPBOARD: u8 = 10;
+const CLIENT_PROGRAM_TITLE: u8 = 11;
+const CLIENT_PROGRAM_BELL: u8 = 12;
+const CLIENT_PROGRAM_ACTIVITY: u8 = 13;
+const CLIENT_PROGRAM_SILENCE: u8 = 14;
+const CLIENT_PROGRAM_CURSOR_STYLE: u8 = 15;
+const CLIENT_PROGRAM_MONITOR_SILENCE: u8 = 16;
+
+/// Some msgs carry handles to things that only make sense inside one process, and others are
+/// ones a process only sends itself.
+fn not_sendable(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput,
+                   format!("{} can't be sent to another process", name))
+}
+
+/// Writes the array header and tag that start a msg with the given number of fields
+fn write_variant<W: Write>(w: &mut W, tag: u8, num_fields: usize) -> io::Result<()> {
+    try!(write_array_len(w, num_fields + 1));
+    tag.encode(w)
+}
+
+/// Checks that a decoded array header had room for the tag plus the variant's fields
+fn check_fields(len: usize, num_fields: usize, name: &str) -> io::Result<()> {
+    if len != num_fields + 1 {
+        return Err(invalid_data(&format!("expected {} to have {} fields but it has {}",
+                                         name,
+                                         num_fields,
+                                         len as isize - 1)));
+    }
+    Ok(())
+}
+
+impl Wire for ServerMsg {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match *self {
+            ServerMsg::Quit => write_variant(w, SERVER_QUIT, 0),
+            ServerMsg::ProgramDamage { .. } => Err(not_sendable("ServerMsg::ProgramDamage")),
+            ServerMsg::ProgramInput { ref program_id, ref bytes } => {
+                try!(write_variant(w, SERVER_PROGRAM_INPUT, 2));
+                try!(program_id.encode(w));
+                write_bin(w, bytes)
+            }
+            ServerMsg::ProgramKill { ref program_id, signal } => {
+                try!(write_variant(w, SERVER_PROGRAM_KILL, 2));
+                try!(program_id.encode(w));
+                signal.encode(w)
+            }
+            ServerMsg::ProgramMoveCursor { .. } => {
+                Err(not_sendable("ServerMsg::ProgramMoveCursor"))
+            }
+            ServerMsg::ProgramRedrawRect { ref program_id, ref rect } => {
+                try!(write_variant(w, SERVER_PROGRAM_REDRAW_RECT, 2));
+                try!(program_id.encode(w));
+                rect.encode(w)
+            }
+            ServerMsg::ProgramStart { ref program_id,
+                                      ref command_and_args,
+                                      ref command_line,
+                                      ref cwd,
+                                      ref env,
+                                      ref size,
+                                      monitor_silence } => {
+                try!(write_variant(w, SERVER_PROGRAM_START, 7));
+                try!(program_id.encode(w));
+                try!(command_and_args.encode(w));
+                try!(command_line.encode(w));
+                try!(cwd.encode(w));
+                try!(env.encode(w));
+                try!(size.encode(w));
+                monitor_silence.encode(w)
+            }
+            ServerMsg::ProgramResize { ref program_id, ref size } => {
+                try!(write_variant(w, SERVER_PROGRAM_RESIZE, 2));
+                try!(program_id.encode(w));
+                size.encode(w)
+            }
+            ServerMsg::ProgramExited { .. } => Err(not_sendable("ServerMsg::ProgramExited")),
+            ServerMsg::ProgramRemove { ref program_id } => {
+                try!(write_variant(w, SERVER_PROGRAM_REMOVE, 1));
+                program_id.encode(w)
+            }
+            ServerMsg::ProgramRestart { ref program_id } => {
+                try!(write_variant(w, SERVER_PROGRAM_RESTART, 1));
+                program_id.encode(w)
+            }
+            ServerMsg::ProgramMonitorSilence { ref program_id, is_on } => {
+                try!(write_variant(w, SERVER_PROGRAM_MONITOR_SILENCE, 2));
+                try!(program_id.encode(w));
+                is_on.encode(w)
+            }
+            // the client is whoever is on the other end of the connection
+            ServerMsg::ProgramSubscribe { ref program_id, .. } => {
+                try!(write_variant(w, SERVER_PROGRAM_SUBSCRIBE, 1));
+                program_id.encode(w)
+            }
+            ServerMsg::ProgramUnsubscribe { ref program_id, .. } => {
+                try!(write_variant(w, SERVER_PROGRAM_UNSUBSCRIBE, 1));
+                program_id.encode(w)
+            }
+            ServerMsg::ProgramScrollbackFetch { ref program_id, start, count, .. } => {
+                try!(write_variant(w, SERVER_PROGRAM_SCROLLBACK_FETCH, 3));
+                try!(program_id.encode(w));
+                try!(start.encode(w));
+                count.encode(w)
+            }
+            ServerMsg::BufferSet { ref name, ref bytes } => {
+                try!(write_variant(w, SERVER_BUFFER_SET, 2));
+                try!(name.encode(w));
+                write_bin(w, bytes)
+            }
+            ServerMsg::BufferPaste { ref name, ref program_id } => {
+                try!(write_variant(w, SERVER_BUFFER_PASTE, 2));
+                try!(name.encode(w));
+                program_id.encode(w)
+            }
+            ServerMsg::ProgramMouse { ref program_id, button, ref pos, is_release } => {
+                try!(write_variant(w, SERVER_PROGRAM_MOUSE, 4));
+                try!(program_id.encode(w));
+                try!(button.encode(w));
+                try!(pos.encode(w));
+                is_release.encode(w)
+            }
+            ServerMsg::ProgramModes { .. } => Err(not_sendable("ServerMsg::ProgramModes")),
+            ServerMsg::ProgramClipboard { .. } => {
+                Err(not_sendable("ServerMsg::ProgramClipboard"))
+            }
+            ServerMsg::ProgramTitle { .. } => Err(not_sendable("ServerMsg::ProgramTitle")),
+            ServerMsg::ProgramCursorStyle { .. } => {
+                Err(not_sendable("ServerMsg::ProgramCursorStyle"))
+            }
+            ServerMsg::ProgramBell { .. } => Err(not_sendable("ServerMsg::ProgramBell")),
+            ServerMsg::ProgramOutput { .. } => Err(not_sendable("ServerMsg::ProgramOutput")),
+            ServerMsg::ProgramSilenceTimeout { .. } => {
+                Err(not_sendable("ServerMsg::ProgramSilenceTimeout"))
+            }
+            ServerMsg::ProgramKillTimeout { .. } => {
+                Err(not_sendable("ServerMsg::ProgramKillTimeout"))
+            }
+            ServerMsg::ProgramScrollback { .. } => {
+                Err(not_sendable("ServerMsg::ProgramScrollback"))
+            }
+            ServerMsg::ClientAdd { .. } => Err(not_sendable("ServerMsg::ClientAdd")),
+            ServerMsg::ClientUpdate { .. } => Err(not_sendable("ServerMsg::ClientUpdate")),
+            ServerMsg::ClientRemote { .. } => Err(not_sendable("ServerMsg::ClientRemote")),
+        }
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<ServerMsg> {
+        let len = try!(read_array_len(r));
+        if len == 0 {
+            return Err(invalid_data("ServerMsg is missing its tag"));
+        }
+
+        let msg = match try!(u8::decode(r)) {
+            SERVER_QUIT => {
+                try!(check_fields(len, 0, "ServerMsg::Quit"));
+                ServerMsg::Quit
+            }
+            SERVER_PROGRAM_INPUT => {
+                try!(check_fields(len, 2, "ServerMsg::ProgramInput"));
+                ServerMsg::ProgramInput {
+                    program_id: try!(Wire::decode(r)),
+                    bytes: try!(read_bin(r)),
+                }
+            }
+            SERVER_PROGRAM_KILL => {
+                try!(check_fields(len, 2, "ServerMsg::ProgramKill"));
+                ServerMsg::ProgramKill {
+                    program_id: try!(Wire::decode(r)),
+                    signal: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_PROGRAM_REDRAW_RECT => {
+                try!(check_fields(len, 2, "ServerMsg::ProgramRedrawRect"));
+                ServerMsg::ProgramRedrawRect {
+                    program_id: try!(Wire::decode(r)),
+                    rect: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_PROGRAM_START => {
+                try!(check_fields(len, 7, "ServerMsg::ProgramStart"));
+                ServerMsg::ProgramStart {
+                    program_id: try!(Wire::decode(r)),
+                    command_and_args: try!(Wire::decode(r)),
+                    command_line: try!(Wire::decode(r)),
+                    cwd: try!(Wire::decode(r)),
+                    env: try!(Wire::decode(r)),
+                    size: try!(Wire::decode(r)),
+                    monitor_silence: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_PROGRAM_RESIZE => {
+                try!(check_fields(len, 2, "ServerMsg::ProgramResize"));
+                ServerMsg::ProgramResize {
+                    program_id: try!(Wire::decode(r)),
+                    size: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_PROGRAM_REMOVE => {
+                try!(check_fields(len, 1, "ServerMsg::ProgramRemove"));
+                ServerMsg::ProgramRemove { program_id: try!(Wire::decode(r)) }
+            }
+            SERVER_PROGRAM_RESTART => {
+                try!(check_fields(len, 1, "ServerMsg::ProgramRestart"));
+                ServerMsg::ProgramRestart { program_id: try!(Wire::decode(r)) }
+            }
+            SERVER_PROGRAM_MONITOR_SILENCE => {
+                try!(check_fields(len, 2, "ServerMsg::ProgramMonitorSilence"));
+                ServerMsg::ProgramMonitorSilence {
+                    program_id: try!(Wire::decode(r)),
+                    is_on: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_PROGRAM_SCROLLBACK_FETCH => {
+                try!(check_fields(len, 3, "ServerMsg::ProgramScrollbackFetch"));
+                ServerMsg::ProgramScrollbackFetch {
+                    client_id: String::new(),
+                    program_id: try!(Wire::decode(r)),
+                    start: try!(Wire::decode(r)),
+                    count: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_BUFFER_SET => {
+                try!(check_fields(len, 2, "ServerMsg::BufferSet"));
+                ServerMsg::BufferSet {
+                    name: try!(Wire::decode(r)),
+                    bytes: try!(read_bin(r)),
+                }
+            }
+            SERVER_BUFFER_PASTE => {
+                try!(check_fields(len, 2, "ServerMsg::BufferPaste"));
+                ServerMsg::BufferPaste {
+                    name: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_PROGRAM_MOUSE => {
+                try!(check_fields(len, 4, "ServerMsg::ProgramMouse"));
+                ServerMsg::ProgramMouse {
+                    program_id: try!(Wire::decode(r)),
+                    button: try!(Wire::decode(r)),
+                    pos: try!(Wire::decode(r)),
+                    is_release: try!(Wire::decode(r)),
+                }
+            }
+            // the reader stamps the client id on, see ServerMsg::from_client
+            SERVER_PROGRAM_SUBSCRIBE => {
+                try!(check_fields(len, 1, "ServerMsg::ProgramSubscribe"));
+                ServerMsg::ProgramSubscribe {
+                    client_id: String::new(),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            SERVER_PROGRAM_UNSUBSCRIBE => {
+                try!(check_fields(len, 1, "ServerMsg::ProgramUnsubscribe"));
+                ServerMsg::ProgramUnsubscribe {
+                    client_id: String::new(),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            tag => return Err(invalid_data(&format!("unknown ServerMsg tag {}", tag))),
+        };
+
+        Ok(msg)
+    }
+}
+
+impl Wire for ClientMsg {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match *self {
+            ClientMsg::Quit => write_variant(w, CLIENT_QUIT, 0),
+            ClientMsg::ServerAdd { .. } => Err(not_sendable("ClientMsg::ServerAdd")),
+            ClientMsg::ServerUpdate { .. } => Err(not_sendable("ClientMsg::ServerUpdate")),
+            ClientMsg::ServerRemove { ref server_id } => {
+                try!(write_variant(w, CLIENT_SERVER_REMOVE, 1));
+                server_id.encode(w)
+            }
+            ClientMsg::ProgramAdd { ref server_id, ref program_id, ref size } => {
+                try!(write_variant(w, CLIENT_PROGRAM_ADD, 3));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                size.encode(w)
+            }
+            ClientMsg::ProgramUpdate { ref server_id, ref program_id } => {
+                try!(write_variant(w, CLIENT_PROGRAM_UPDATE, 2));
+                try!(server_id.encode(w));
+                program_id.encode(w)
+            }
+            ClientMsg::ProgramRemove { ref server_id, ref program_id } => {
+                try!(write_variant(w, CLIENT_PROGRAM_REMOVE, 2));
+                try!(server_id.encode(w));
+                program_id.encode(w)
+            }
+            ClientMsg::ProgramResize { ref server_id, ref program_id, ref size } => {
+                try!(write_variant(w, CLIENT_PROGRAM_RESIZE, 3));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                size.encode(w)
+            }
+            ClientMsg::ProgramExited { ref server_id, ref program_id, status } => {
+                try!(write_variant(w, CLIENT_PROGRAM_EXITED, 3));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                status.encode(w)
+            }
+            ClientMsg::ProgramScrollback { ref server_id,
+                                           ref program_id,
+                                           start,
+                                           ref lines,
+                                           total } => {
+                try!(write_variant(w, CLIENT_PROGRAM_SCROLLBACK, 5));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                try!(start.encode(w));
+                try!(lines.encode(w));
+                total.encode(w)
+            }
+            ClientMsg::Clipboard { ref server_id, ref bytes } => {
+                try!(write_variant(w, CLIENT_CLIPBOARD, 2));
+                try!(server_id.encode(w));
+                write_bin(w, bytes)
+            }
+            ClientMsg::ProgramTitle { ref server_id, ref program_id, ref title, ref icon_name } => {
+                try!(write_variant(w, CLIENT_PROGRAM_TITLE, 4));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                try!(title.encode(w));
+                icon_name.encode(w)
+            }
+            ClientMsg::ProgramBell { ref server_id, ref program_id } => {
+                try!(write_variant(w, CLIENT_PROGRAM_BELL, 2));
+                try!(server_id.encode(w));
+                program_id.encode(w)
+            }
+            ClientMsg::ProgramActivity { ref server_id, ref program_id } => {
+                try!(write_variant(w, CLIENT_PROGRAM_ACTIVITY, 2));
+                try!(server_id.encode(w));
+                program_id.encode(w)
+            }
+            ClientMsg::ProgramSilence { ref server_id, ref program_id } => {
+                try!(write_variant(w, CLIENT_PROGRAM_SILENCE, 2));
+                try!(server_id.encode(w));
+                program_id.encode(w)
+            }
+            ClientMsg::ProgramCursorStyle { ref server_id, ref program_id, style } => {
+                try!(write_variant(w, CLIENT_PROGRAM_CURSOR_STYLE, 3));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                style.encode(w)
+            }
+            ClientMsg::ProgramMonitorSilence { ref server_id, ref program_id, is_on } => {
+                try!(write_variant(w, CLIENT_PROGRAM_MONITOR_SILENCE, 3));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                is_on.encode(w)
+            }
+            ClientMsg::ProgramDamage { ref server_id, ref program_id, ref cells, ref rect } => {
+                try!(write_variant(w, CLIENT_PROGRAM_DAMAGE, 4));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                try!(cells.encode(w));
+                rect.encode(w)
+            }
+            ClientMsg::ProgramMoveCursor { ref server_id,
+                                           ref program_id,
+                                           ref new,
+                                           ref old,
+                                           is_visible } => {
+                try!(write_variant(w, CLIENT_PROGRAM_MOVE_CURSOR, 5));
+                try!(server_id.encode(w));
+                try!(program_id.encode(w));
+                try!(new.encode(w));
+                try!(old.encode(w));
+                is_visible.encode(w)
+            }
+            // a server has no business typing for the user or repainting the client's screen
+            ClientMsg::UserInput { .. } => Err(not_sendable("ClientMsg::UserInput")),
+            ClientMsg::Clear => Err(not_sendable("ClientMsg::Clear")),
+            ClientMsg::LayoutDamage => Err(not_sendable("ClientMsg::LayoutDamage")),
+            ClientMsg::LayoutSwap { .. } => Err(not_sendable("ClientMsg::LayoutSwap")),
+            ClientMsg::StatusLineDamage => Err(not_sendable("ClientMsg::StatusLineDamage")),
+            ClientMsg::TtyResize { .. } => Err(not_sendable("ClientMsg::TtyResize")),
+        }
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<ClientMsg> {
+        let len = try!(read_array_len(r));
+        if len == 0 {
+            return Err(invalid_data("ClientMsg is missing its tag"));
+        }
+
+        let msg = match try!(u8::decode(r)) {
+            CLIENT_QUIT => {
+                try!(check_fields(len, 0, "ClientMsg::Quit"));
+                ClientMsg::Quit
+            }
+            CLIENT_SERVER_REMOVE => {
+                try!(check_fields(len, 1, "ClientMsg::ServerRemove"));
+                ClientMsg::ServerRemove { server_id: try!(Wire::decode(r)) }
+            }
+            CLIENT_PROGRAM_ADD => {
+                try!(check_fields(len, 3, "ClientMsg::ProgramAdd"));
+                ClientMsg::ProgramAdd {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    size: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_UPDATE => {
+                try!(check_fields(len, 2, "ClientMsg::ProgramUpdate"));
+                ClientMsg::ProgramUpdate {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_REMOVE => {
+                try!(check_fields(len, 2, "ClientMsg::ProgramRemove"));
+                ClientMsg::ProgramRemove {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_RESIZE => {
+                try!(check_fields(len, 3, "ClientMsg::ProgramResize"));
+                ClientMsg::ProgramResize {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    size: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_EXITED => {
+                try!(check_fields(len, 3, "ClientMsg::ProgramExited"));
+                ClientMsg::ProgramExited {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    status: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_SCROLLBACK => {
+                try!(check_fields(len, 5, "ClientMsg::ProgramScrollback"));
+                ClientMsg::ProgramScrollback {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    start: try!(Wire::decode(r)),
+                    lines: try!(Wire::decode(r)),
+                    total: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_CLIPBOARD => {
+                try!(check_fields(len, 2, "ClientMsg::Clipboard"));
+                ClientMsg::Clipboard {
+                    server_id: try!(Wire::decode(r)),
+                    bytes: try!(read_bin(r)),
+                }
+            }
+            CLIENT_PROGRAM_TITLE => {
+                try!(check_fields(len, 4, "ClientMsg::ProgramTitle"));
+                ClientMsg::ProgramTitle {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    title: try!(Wire::decode(r)),
+                    icon_name: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_BELL => {
+                try!(check_fields(len, 2, "ClientMsg::ProgramBell"));
+                ClientMsg::ProgramBell {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_ACTIVITY => {
+                try!(check_fields(len, 2, "ClientMsg::ProgramActivity"));
+                ClientMsg::ProgramActivity {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_SILENCE => {
+                try!(check_fields(len, 2, "ClientMsg::ProgramSilence"));
+                ClientMsg::ProgramSilence {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_CURSOR_STYLE => {
+                try!(check_fields(len, 3, "ClientMsg::ProgramCursorStyle"));
+                ClientMsg::ProgramCursorStyle {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    style: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_MONITOR_SILENCE => {
+                try!(check_fields(len, 3, "ClientMsg::ProgramMonitorSilence"));
+                ClientMsg::ProgramMonitorSilence {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    is_on: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_DAMAGE => {
+                try!(check_fields(len, 4, "ClientMsg::ProgramDamage"));
+                ClientMsg::ProgramDamage {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    cells: try!(Wire::decode(r)),
+                    rect: try!(Wire::decode(r)),
+                }
+            }
+            CLIENT_PROGRAM_MOVE_CURSOR => {
+                try!(check_fields(len, 5, "ClientMsg::ProgramMoveCursor"));
+                ClientMsg::ProgramMoveCursor {
+                    server_id: try!(Wire::decode(r)),
+                    program_id: try!(Wire::decode(r)),
+                    new: try!(Wire::decode(r)),
+                    old: try!(Wire::decode(r)),
+                    is_visible: try!(Wire::decode(r)),
+                }
+            }
+            tag => return Err(invalid_data(&format!("unknown ClientMsg tag {}", tag))),
+        };
+
+        Ok(msg)
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::prelude::*;
+use std::io;
+use super::*;
+use vterm_sys::{ColorRGB, Pos, Rect, ScreenCell, Size};
+
+impl Wire for Pos {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_array_len(w, 2));
+        try!(self.x.encode(w));
+        self.y.encode(w)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<Pos> {
+        try!(expect_array_len(r, 2, "Pos"));
+        let x = try!(usize::decode(r));
+        let y = try!(usize::decode(r));
+        Ok(Pos::new(x, y))
+    }
+}
+
+impl Wire for Size {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_array_len(w, 2));
+        try!(self.width.encode(w));
+        self.height.encode(w)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<Size> {
+        try!(expect_array_len(r, 2, "Size"));
+        let width = try!(usize::decode(r));
+        let height = try!(usize::decode(r));
+        Ok(Size::new(width, height))
+    }
+}
+
+impl Wire for Rect {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_array_len(w, 2));
+        try!(self.origin.encode(w));
+        self.size.encode(w)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<Rect> {
+        try!(expect_array_len(r, 2, "Rect"));
+        let origin = try!(Pos::decode(r));
+        let size = try!(Size::decode(r));
+        Ok(Rect::new(origin, size))
+    }
+}
+
+impl Wire for ColorRGB {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_array_len(w, 3));
+        try!(self.red.encode(w));
+        try!(self.green.encode(w));
+        self.blue.encode(w)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<ColorRGB> {
+        try!(expect_array_len(r, 3, "ColorRGB"));
+        Ok(ColorRGB {
+            red: try!(u8::decode(r)),
+            green: try!(u8::decode(r)),
+            blue: try!(u8::decode(r)),
+        })
+    }
+}
+
+/// Cells are the bulk of what gets sent, so they are a flat array rather than nesting attrs.
+impl Wire for ScreenCell {
+    fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write_array_len(w, 15));
+        try!(write_bin(w, &self.chars));
+        try!(self.width.encode(w));
+
+        try!(self.attrs.bold.encode(w));
+        try!(self.attrs.underline.encode(w));
+        try!(self.attrs.italic.encode(w));
+        try!(self.attrs.blink.encode(w));
+        try!(self.attrs.reverse.encode(w));
+        try!(self.attrs.strike.encode(w));
+        try!(self.attrs.font.encode(w));
+        try!(self.attrs.dwl.encode(w));
+        try!(self.attrs.dhl.encode(w));
+
+        try!(self.fg_palette.encode(w));
+        try!(self.bg_palette.encode(w));
+        try!(self.fg_rgb.encode(w));
+        self.bg_rgb.encode(w)
+    }
+
+    fn decode<R: Read>(r: &mut R) -> io::Result<ScreenCell> {
+        try!(expect_array_len(r, 15, "ScreenCell"));
+        let mut cell: ScreenCell = Default::default();
+
+        cell.chars = try!(read_bin(r));
+        cell.width = try!(Wire::decode(r));
+
+        cell.attrs.bold = try!(Wire::decode(r));
+        cell.attrs.underline = try!(Wire::decode(r));
+        cell.attrs.italic = try!(Wire::decode(r));
+        cell.attrs.blink = try!(Wire::decode(r));
+        cell.attrs.reverse = try!(Wire::decode(r));
+        cell.attrs.strike = try!(Wire::decode(r));
+        cell.attrs.font = try!(Wire::decode(r));
+        cell.attrs.dwl = try!(Wire::decode(r));
+        cell.attrs.dhl = try!(Wire::decode(r));
+
+        cell.fg_palette = try!(Wire::decode(r));
+        cell.bg_palette = try!(Wire::decode(r));
+        cell.fg_rgb = try!(Wire::decode(r));
+        cell.bg_rgb = try!(Wire::decode(r));
+
+        Ok(cell)
+    }
+}
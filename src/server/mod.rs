@@ -1,11 +1,19 @@
 mod program;
+mod socket_listener;
 
 use vterm_sys::{ScreenCell, Rect, Pos, Size};
 use self::program::*;
-use std::io::prelude::*;
+pub use self::socket_listener::*;
+use libc;
+use std::collections::HashMap;
+use std::io;
 use std::os::unix::prelude::*;
+use std::path::PathBuf;
 use std::sync::mpsc::*;
 use std::thread;
+use std::time::{Duration, Instant};
+use term::terminfo::TermInfo;
+use transport::Tx;
 
 pub enum ServerMsg {
     Quit,
@@ -19,10 +27,73 @@ pub enum ServerMsg {
         program_id: String,
         bytes: Vec<u8>,
     },
+    /// The vte worker saw the program turn DEC private modes on or off. These are the ones on.
+    ProgramModes {
+        program_id: String,
+        modes: Vec<u16>,
+    },
+    /// The program set the clipboard with OSC 52. It goes in the clipboard paste buffer.
+    ProgramClipboard {
+        program_id: String,
+        bytes: Vec<u8>,
+    },
+    /// The program rang the bell
+    ProgramBell {
+        program_id: String,
+    },
+    /// The program wrote something
+    ProgramOutput {
+        program_id: String,
+    },
+    /// The silence period since the program became active is up. It has the pid so a restarted
+    /// program isn't mixed up with the old one.
+    ProgramSilenceTimeout {
+        program_id: String,
+        child_pid: i32,
+    },
+    /// The program set the cursor style with DECSCUSR
+    ProgramCursorStyle {
+        program_id: String,
+        style: u8,
+    },
+    /// The program set its title or icon name. None leaves that one as it was.
+    ProgramTitle {
+        program_id: String,
+        title: Option<String>,
+        icon_name: Option<String>,
+    },
+    /// Keep the bytes in the named paste buffer, replacing what was there. Every client can paste
+    /// from it.
+    BufferSet {
+        name: String,
+        bytes: Vec<u8>,
+    },
+    /// Write the named paste buffer to the program, bracketed if the program asked for that
+    BufferPaste {
+        name: String,
+        program_id: String,
+    },
+    /// A mouse event over the program's pane. button is an SGR button byte, and pos is 0 based and
+    /// relative to the program's screen. It is only passed on if the program turned on mouse
+    /// reporting.
+    ProgramMouse {
+        program_id: String,
+        button: u8,
+        pos: Pos,
+        is_release: bool,
+    },
+    /// Send the signal to the program's process group. A SIGTERM is followed by a SIGKILL if the
+    /// program is still running after the server's grace period.
     ProgramKill {
         program_id: String,
         signal: u8,
     },
+    /// The grace period after a SIGTERM is up. It has the pid so a restarted program isn't killed
+    /// by mistake.
+    ProgramKillTimeout {
+        program_id: String,
+        child_pid: i32,
+    },
     ProgramMoveCursor {
         program_id: String,
         new: Pos,
@@ -33,9 +104,66 @@ pub enum ServerMsg {
         program_id: String,
         rect: Rect,
     },
+    /// Start the program at the size of the pane it will be shown in. A command line is run by
+    /// the server user's shell instead of command_and_args, and if neither is given the shell
+    /// itself is run. Each env entry is `NAME=value`, or a bare `NAME` to unset it.
     ProgramStart {
         program_id: String,
         command_and_args: Vec<String>,
+        command_line: Option<String>,
+        cwd: Option<String>,
+        env: Vec<String>,
+        size: Size,
+        monitor_silence: bool,
+    },
+    ProgramResize {
+        program_id: String,
+        size: Size,
+    },
+    /// The program's process has exited. Its final screen is kept until it is removed.
+    ProgramExited {
+        program_id: String,
+        status: u8,
+    },
+    /// Forget an exited program
+    ProgramRemove {
+        program_id: String,
+    },
+    /// Run an exited program's command again, in the same pane
+    ProgramRestart {
+        program_id: String,
+    },
+    /// Tell clients when the program goes quiet after writing something, or stop telling them.
+    /// It is off unless ProgramStart turned it on, since a shell waiting at its prompt is always
+    /// quiet.
+    ProgramMonitorSilence {
+        program_id: String,
+        is_on: bool,
+    },
+    /// Ask for up to count lines of the program's scrollback, counting back from the top of its
+    /// screen, so 0 is the newest line. Only the asking client gets the reply.
+    ProgramScrollbackFetch {
+        client_id: String,
+        program_id: String,
+        start: usize,
+        count: usize,
+    },
+    /// The vte worker's reply to ProgramScrollbackFetch
+    ProgramScrollback {
+        client_id: String,
+        program_id: String,
+        start: usize,
+        lines: Vec<Vec<ScreenCell>>,
+        total: usize,
+    },
+    /// Start sending the client damage for the program, beginning with its whole screen
+    ProgramSubscribe {
+        client_id: String,
+        program_id: String,
+    },
+    ProgramUnsubscribe {
+        client_id: String,
+        program_id: String,
     },
 
     ClientAdd {
@@ -49,29 +177,125 @@ pub enum ServerMsg {
     },
 }
 
+impl ServerMsg {
+    /// Msgs that act on behalf of a client take its id from the connection they came in on rather
+    /// than from the msg, so one client can't pass itself off as another.
+    pub fn from_client(self, client_id: &str) -> ServerMsg {
+        match self {
+            ServerMsg::ProgramSubscribe { program_id, .. } => {
+                ServerMsg::ProgramSubscribe {
+                    client_id: client_id.to_string(),
+                    program_id: program_id,
+                }
+            }
+            ServerMsg::ProgramUnsubscribe { program_id, .. } => {
+                ServerMsg::ProgramUnsubscribe {
+                    client_id: client_id.to_string(),
+                    program_id: program_id,
+                }
+            }
+            ServerMsg::ProgramScrollbackFetch { program_id, start, count, .. } => {
+                ServerMsg::ProgramScrollbackFetch {
+                    client_id: client_id.to_string(),
+                    program_id: program_id,
+                    start: start,
+                    count: count,
+                }
+            }
+            msg => msg,
+        }
+    }
+}
+
 /// a server's representation of a client
 pub struct Client {
     pub id: String,
+    pub tx: Tx<::client::ClientMsg>,
+    /// ids of programs the client wants damage for
+    subscriptions: Vec<String>,
+}
+
+impl Client {
+    pub fn new(id: &str, tx: Tx<::client::ClientMsg>) -> Client {
+        Client {
+            id: id.to_string(),
+            tx: tx,
+            subscriptions: vec![],
+        }
+    }
+
+    pub fn is_subscribed(&self, program_id: &str) -> bool {
+        self.subscriptions.iter().any(|id| id == program_id)
+    }
+}
 
-    /// replace with with cap'n proto or whatever
-    pub tx: Sender<::client::ClientMsg>,
+/// The paste buffer that copy mode writes to
+pub const DEFAULT_BUFFER: &'static str = "default";
+/// The paste buffer that programs setting the clipboard write to. It is kept apart from the
+/// default one, so a program can't change what is pasted into another.
+pub const CLIPBOARD_BUFFER: &'static str = "clipboard";
+
+const PASTE_START: &'static [u8] = b"\x1b[200~";
+const PASTE_END: &'static [u8] = b"\x1b[201~";
+
+/// Settings that change how the server treats its programs
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// How long a program has to exit after a SIGTERM before it gets a SIGKILL
+    pub kill_grace_period: Duration,
+    /// How many lines that scrolled off the top of each program's screen are kept
+    pub scrollback_lines: usize,
+    /// How long a program has to be quiet before clients are told it has gone silent
+    pub silence_period: Duration,
+    /// What programs are told the terminal is. It should describe vterm, not the client's terminal.
+    pub term: String,
+    /// Where clients connect, so programs can find their way back to the server
+    pub socket_path: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            kill_grace_period: Duration::from_secs(5),
+            scrollback_lines: 10000,
+            silence_period: Duration::from_secs(10),
+            term: default_term(),
+            socket_path: None,
+        }
+    }
+}
+
+/// The intermix terminfo entry if it has been installed, see doc/terminfo.txt. Otherwise the
+/// nearest common one, which vterm mostly keeps up with.
+pub fn default_term() -> String {
+    if TermInfo::from_name("intermix").is_ok() {
+        "intermix".to_string()
+    } else {
+        "xterm-256color".to_string()
+    }
 }
 
 pub struct Server {
+    /// How clients tell this server apart from the others they are connected to
+    id: String,
+    config: ServerConfig,
     tx: Sender<ServerMsg>,
     rx: Receiver<ServerMsg>,
     clients: Vec<Client>,
     programs: Vec<Program>,
+    /// named paste buffers, shared by every client
+    buffers: HashMap<String, Vec<u8>>,
 }
 
 impl Server {
-    pub fn spawn() -> (Sender<ServerMsg>, thread::JoinHandle<()>) {
+    pub fn spawn(id: &str, config: ServerConfig) -> (Sender<ServerMsg>, thread::JoinHandle<()>) {
         let (tx, rx) = channel::<ServerMsg>();
         let tx_clone = tx.clone();
+        let id = id.to_string();
 
-        info!("spawning server");
+        info!("spawning server {:?}", id);
         let handle = thread::spawn(move || {
-            let mut server = Server::new(tx, rx);
+            let mut server = Server::new(id, config, tx, rx);
             server.enter_listener_loop();
             info!("exiting server");
         });
@@ -79,12 +303,19 @@ impl Server {
         (tx_clone, handle)
     }
 
-    fn new(tx: Sender<ServerMsg>, rx: Receiver<ServerMsg>) -> Server {
+    fn new(id: String,
+           config: ServerConfig,
+           tx: Sender<ServerMsg>,
+           rx: Receiver<ServerMsg>)
+           -> Server {
         Server {
+            id: id,
+            config: config,
             tx: tx,
             rx: rx,
             clients: vec![],
             programs: vec![],
+            buffers: HashMap::new(),
         }
     }
 
@@ -101,66 +332,701 @@ impl Server {
                 ServerMsg::ProgramDamage { program_id, cells, rect } => {
                     // What is Vec going to do with the cell data on the heap? Hopefully it will
                     // leave it alone?
-                    self.send_msg_to_clients(::client::ClientMsg::ProgramDamage {
-                                                 program_id: program_id,
-                                                 cells: cells,
-                                                 rect: rect,
-                                             },
-                                             true);
+                    let msg = ::client::ClientMsg::ProgramDamage {
+                        server_id: self.id.clone(),
+                        program_id: program_id.clone(),
+                        cells: cells,
+                        rect: rect,
+                    };
+                    self.send_msg_to_subscribers(&program_id, msg);
                 }
                 ServerMsg::ProgramInput { program_id, bytes } => {
                     self.program_input(program_id, bytes)
                 }
-                ServerMsg::ProgramKill { .. } => {}
-                ServerMsg::ProgramMoveCursor { .. } => {}
-                ServerMsg::ProgramRedrawRect { .. } => {}
-
-                // need client id here
-                ServerMsg::ProgramStart { program_id, command_and_args } => {
-                    self.start_program(program_id, command_and_args)
+                ServerMsg::ProgramModes { program_id, modes } => {
+                    if let Some(program) = self.programs.iter_mut().find(|p| p.id == program_id) {
+                        program.dec_modes = modes;
+                    }
+                }
+                ServerMsg::ProgramClipboard { program_id, bytes } => {
+                    self.program_clipboard(program_id, bytes)
+                }
+                ServerMsg::ProgramBell { program_id } => {
+                    let msg = ::client::ClientMsg::ProgramBell {
+                        server_id: self.id.clone(),
+                        program_id: program_id,
+                    };
+                    self.send_msg_to_clients(msg);
+                }
+                ServerMsg::ProgramOutput { program_id } => self.program_output(program_id),
+                ServerMsg::ProgramSilenceTimeout { program_id, child_pid } => {
+                    self.program_silence_timeout(program_id, child_pid)
+                }
+                ServerMsg::ProgramCursorStyle { program_id, style } => {
+                    self.program_cursor_style(program_id, style)
+                }
+                ServerMsg::ProgramTitle { program_id, title, icon_name } => {
+                    self.program_title(program_id, title, icon_name)
+                }
+                ServerMsg::BufferSet { name, bytes } => {
+                    trace!("setting paste buffer {:?} to {} bytes", name, bytes.len());
+                    self.buffers.insert(name, bytes);
+                }
+                ServerMsg::BufferPaste { name, program_id } => self.buffer_paste(name, program_id),
+                ServerMsg::ProgramMouse { program_id, button, pos, is_release } => {
+                    self.program_mouse(program_id, button, pos, is_release)
+                }
+                ServerMsg::ProgramMonitorSilence { program_id, is_on } => {
+                    self.program_monitor_silence(program_id, is_on)
+                }
+                ServerMsg::ProgramKill { program_id, signal } => {
+                    self.program_kill(program_id, signal)
+                }
+                ServerMsg::ProgramKillTimeout { program_id, child_pid } => {
+                    self.program_kill_timeout(program_id, child_pid)
+                }
+                ServerMsg::ProgramMoveCursor { program_id, new, old, is_visible } => {
+                    let msg = ::client::ClientMsg::ProgramMoveCursor {
+                        server_id: self.id.clone(),
+                        program_id: program_id.clone(),
+                        new: new,
+                        old: old,
+                        is_visible: is_visible,
+                    };
+                    self.send_msg_to_subscribers(&program_id, msg);
+                }
+                ServerMsg::ProgramRedrawRect { program_id, rect } => {
+                    self.program_redraw_rect(program_id, rect)
                 }
 
-                ServerMsg::ClientAdd { client } => {
-                    self.clients.push(client);
+                ServerMsg::ProgramStart { program_id,
+                                          command_and_args,
+                                          command_line,
+                                          cwd,
+                                          env,
+                                          size,
+                                          monitor_silence } => {
+                    let command = Command::new(command_and_args, command_line, cwd, env);
+                    self.start_program(program_id, command, size, monitor_silence)
+                }
+                ServerMsg::ProgramResize { program_id, size } => {
+                    self.program_resize(program_id, size)
+                }
+                ServerMsg::ProgramExited { program_id, status } => {
+                    self.program_exited(program_id, status)
                 }
-                ServerMsg::ClientUpdate { .. } => {}
-                ServerMsg::ClientRemote { .. } => {}
+                ServerMsg::ProgramRemove { program_id } => self.remove_program(program_id),
+                ServerMsg::ProgramRestart { program_id } => self.restart_program(program_id),
+                ServerMsg::ProgramScrollbackFetch { client_id, program_id, start, count } => {
+                    self.program_scrollback_fetch(client_id, program_id, start, count)
+                }
+                ServerMsg::ProgramScrollback { client_id, program_id, start, lines, total } => {
+                    let msg = ::client::ClientMsg::ProgramScrollback {
+                        server_id: self.id.clone(),
+                        program_id: program_id,
+                        start: start,
+                        lines: lines,
+                        total: total,
+                    };
+                    self.send_msg_to_clients_where(msg, |c| c.id == client_id);
+                }
+                ServerMsg::ProgramSubscribe { client_id, program_id } => {
+                    self.subscribe(client_id, program_id)
+                }
+                ServerMsg::ProgramUnsubscribe { client_id, program_id } => {
+                    self.unsubscribe(client_id, program_id)
+                }
+
+                ServerMsg::ClientAdd { client } => self.add_client(client),
+                ServerMsg::ClientUpdate { client } => self.update_client(client),
+                ServerMsg::ClientRemote { client_id } => self.remove_client(client_id),
             }
         }
     }
 
+    /// A client may be attaching after programs were started, so it's told about all of them. It
+    /// gets their screens when it subscribes.
+    fn add_client(&mut self, client: Client) {
+        info!("adding client {:?}", client.id);
+
+        for program in &self.programs {
+            let mut result = client.tx.send(::client::ClientMsg::ProgramAdd {
+                server_id: self.id.clone(),
+                program_id: program.id.clone(),
+                size: program.size.clone(),
+            });
+            if !program.title.is_empty() || !program.icon_name.is_empty() {
+                result = result.and_then(|_| {
+                    client.tx.send(::client::ClientMsg::ProgramTitle {
+                        server_id: self.id.clone(),
+                        program_id: program.id.clone(),
+                        title: program.title.clone(),
+                        icon_name: program.icon_name.clone(),
+                    })
+                });
+            }
+            if program.cursor_style != 0 {
+                result = result.and_then(|_| {
+                    client.tx.send(::client::ClientMsg::ProgramCursorStyle {
+                        server_id: self.id.clone(),
+                        program_id: program.id.clone(),
+                        style: program.cursor_style,
+                    })
+                });
+            }
+            if program.monitor_silence {
+                result = result.and_then(|_| {
+                    client.tx.send(::client::ClientMsg::ProgramMonitorSilence {
+                        server_id: self.id.clone(),
+                        program_id: program.id.clone(),
+                        is_on: true,
+                    })
+                });
+            }
+            if let Some(status) = program.exit_status {
+                result = result.and_then(|_| {
+                    client.tx.send(::client::ClientMsg::ProgramExited {
+                        server_id: self.id.clone(),
+                        program_id: program.id.clone(),
+                        status: status,
+                    })
+                });
+            }
+            if result.is_err() {
+                warn!("couldnt tell client {:?} about program {:?}", client.id, program.id);
+            }
+        }
+
+        self.clients.push(client);
+    }
+
+    /// Replace the client with the same id, for example when it has reconnected.
+    fn update_client(&mut self, client: Client) {
+        if let Some(existing) = self.clients.iter_mut().find(|c| c.id == client.id) {
+            info!("updating client {:?}", client.id);
+            *existing = client;
+            return;
+        }
+
+        warn!("updating unknown client {:?}, adding it instead", client.id);
+        self.add_client(client);
+    }
+
+    fn remove_client(&mut self, client_id: String) {
+        info!("removing client {:?}", client_id);
+        self.clients.retain(|c| c.id != client_id);
+    }
+
+    fn subscribe(&mut self, client_id: String, program_id: String) {
+        trace!("client {:?} subscribing to {:?}", client_id, program_id);
+
+        if let Some(client) = self.clients.iter_mut().find(|c| c.id == client_id) {
+            if !client.is_subscribed(&program_id) {
+                client.subscriptions.push(program_id.clone());
+            }
+        } else {
+            warn!("unknown client {:?} can't subscribe", client_id);
+            return;
+        }
+
+        // Other subscribers get the redraw too. It's harmless, just redundant.
+        if let Some(program) = self.programs.iter().find(|p| p.id == program_id) {
+            program.redraw();
+        } else {
+            warn!("client {:?} subscribed to unknown program {:?}", client_id, program_id);
+        }
+    }
+
+    fn unsubscribe(&mut self, client_id: String, program_id: String) {
+        trace!("client {:?} unsubscribing from {:?}", client_id, program_id);
+
+        if let Some(client) = self.clients.iter_mut().find(|c| c.id == client_id) {
+            client.subscriptions.retain(|id| *id != program_id);
+        } else {
+            warn!("unknown client {:?} can't unsubscribe", client_id);
+        }
+    }
+
     fn program_input(&mut self, program_id: String, bytes: Vec<u8>) {
         trace!("input for program {:?}", program_id);
-        if let Some(mut program) = self.programs.iter_mut().find(|p| p.id == program_id) {
-            program.pty.write_all(bytes.as_slice()).unwrap();
+        if let Some(program) = self.programs.iter().find(|p| p.id == program_id) {
+            program.write(bytes);
         } else {
             trace!("couldnt send input to unknown program {:?}", program_id);
         }
     }
 
-    fn send_msg_to_clients(&self, msg: ::client::ClientMsg, hard: bool) {
+    /// Encoded the way the program asked for, so programs that didn't turn on mouse reporting get
+    /// nothing
+    fn program_mouse(&mut self, program_id: String, button: u8, pos: Pos, is_release: bool) {
+        let program = match self.programs.iter_mut().find(|p| p.id == program_id) {
+            Some(program) => program,
+            None => {
+                trace!("couldnt send mouse event to unknown program {:?}", program_id);
+                return;
+            }
+        };
+        if program.exit_status.is_some() {
+            return;
+        }
+
+        if let Some(bytes) = encode_mouse(&program.dec_modes, button, &pos, is_release) {
+            program.write(bytes);
+        }
+    }
+
+    /// Clients are told too, so they can pass it on to the terminal they are running in
+    fn program_clipboard(&mut self, program_id: String, bytes: Vec<u8>) {
+        trace!("program {:?} set the clipboard to {} bytes", program_id, bytes.len());
+        self.buffers.insert(CLIPBOARD_BUFFER.to_string(), bytes.clone());
+
+        self.send_msg_to_clients(::client::ClientMsg::Clipboard {
+            server_id: self.id.clone(),
+            bytes: bytes,
+        });
+    }
+
+    /// Clients are told when a quiet program starts writing again, rather than about every write.
+    /// Then it is watched for going quiet again.
+    fn program_output(&mut self, program_id: String) {
+        let child_pid = match self.programs.iter_mut().find(|p| p.id == program_id) {
+            Some(program) => {
+                let was_quiet = program.last_output.is_none();
+                program.last_output = Some(Instant::now());
+                if !was_quiet {
+                    return;
+                }
+                program.child_pid
+            }
+            None => return,
+        };
+
+        trace!("program {:?} is active", program_id);
+        self.send_msg_to_clients(::client::ClientMsg::ProgramActivity {
+            server_id: self.id.clone(),
+            program_id: program_id.clone(),
+        });
+        self.start_silence_timer(program_id, child_pid, self.config.silence_period);
+    }
+
+    fn start_silence_timer(&self, program_id: String, child_pid: i32, period: Duration) {
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            thread::sleep(period);
+            tx.send(ServerMsg::ProgramSilenceTimeout {
+                  program_id: program_id,
+                  child_pid: child_pid,
+              })
+              .ok();
+        });
+    }
+
+    /// If the program wrote something since the timer started, the timer is started again for
+    /// what's left of the silence period after that. A quiet program that isn't monitored for
+    /// silence isn't reported, but its next output still counts as activity.
+    fn program_silence_timeout(&mut self, program_id: String, child_pid: i32) {
+        let period = self.config.silence_period;
+        let remaining = match self.programs
+                                  .iter_mut()
+                                  .find(|p| p.id == program_id && p.child_pid == child_pid) {
+            Some(program) => {
+                let quiet_for = match program.last_output {
+                    Some(last_output) => last_output.elapsed(),
+                    None => return,
+                };
+                if quiet_for >= period {
+                    program.last_output = None;
+                    if !program.monitor_silence {
+                        return;
+                    }
+                    None
+                } else {
+                    Some(period - quiet_for)
+                }
+            }
+            None => return,
+        };
+
+        match remaining {
+            Some(remaining) => self.start_silence_timer(program_id, child_pid, remaining),
+            None => {
+                trace!("program {:?} is silent", program_id);
+                self.send_msg_to_clients(::client::ClientMsg::ProgramSilence {
+                    server_id: self.id.clone(),
+                    program_id: program_id,
+                });
+            }
+        }
+    }
+
+    /// Every client is told, so they all show the same setting whichever one changed it
+    fn program_monitor_silence(&mut self, program_id: String, is_on: bool) {
+        match self.programs.iter_mut().find(|p| p.id == program_id) {
+            Some(program) => {
+                trace!("monitoring program {:?} for silence: {}", program_id, is_on);
+                program.monitor_silence = is_on;
+            }
+            None => {
+                trace!("couldnt monitor unknown program {:?} for silence", program_id);
+                return;
+            }
+        }
+
+        self.send_msg_to_clients(::client::ClientMsg::ProgramMonitorSilence {
+            server_id: self.id.clone(),
+            program_id: program_id,
+            is_on: is_on,
+        });
+    }
+
+    /// Every client is told, like with titles, so whichever has the program selected can draw its
+    /// cursor the same way
+    fn program_cursor_style(&mut self, program_id: String, style: u8) {
+        match self.programs.iter_mut().find(|p| p.id == program_id) {
+            Some(program) => program.cursor_style = style,
+            None => {
+                trace!("couldnt set the cursor style of unknown program {:?}", program_id);
+                return;
+            }
+        }
+
+        self.send_msg_to_clients(::client::ClientMsg::ProgramCursorStyle {
+            server_id: self.id.clone(),
+            program_id: program_id,
+            style: style,
+        });
+    }
+
+    /// Every client is told, since titles are shown for programs whether or not they're visible
+    fn program_title(&mut self,
+                     program_id: String,
+                     title: Option<String>,
+                     icon_name: Option<String>) {
+        let msg = match self.programs.iter_mut().find(|p| p.id == program_id) {
+            Some(program) => {
+                program.title = title.unwrap_or(program.title.clone());
+                program.icon_name = icon_name.unwrap_or(program.icon_name.clone());
+                trace!("program {:?} has the title {:?}", program_id, program.title);
+
+                ::client::ClientMsg::ProgramTitle {
+                    server_id: self.id.clone(),
+                    program_id: program_id.clone(),
+                    title: program.title.clone(),
+                    icon_name: program.icon_name.clone(),
+                }
+            }
+            None => {
+                trace!("couldnt set the title of unknown program {:?}", program_id);
+                return;
+            }
+        };
+
+        self.send_msg_to_clients(msg);
+    }
+
+    /// Programs that asked for bracketed paste get the buffer between ESC [ 200 ~ and ESC [ 201 ~.
+    /// Any end marker in the buffer itself is taken out, so a paste can't pretend to be typed.
+    fn buffer_paste(&mut self, name: String, program_id: String) {
+        let buffer = match self.buffers.get(&name) {
+            Some(buffer) => buffer.clone(),
+            None => {
+                info!("no paste buffer named {:?}", name);
+                return;
+            }
+        };
+
+        let program = match self.programs.iter().find(|p| p.id == program_id) {
+            Some(program) if program.exit_status.is_none() => program,
+            _ => {
+                trace!("couldnt paste into program {:?}", program_id);
+                return;
+            }
+        };
+
+        if program.dec_modes.contains(&BRACKETED_PASTE) {
+            let mut bytes = PASTE_START.to_vec();
+            bytes.extend(without(&buffer, PASTE_END));
+            bytes.extend(PASTE_END.iter());
+            program.write(bytes);
+        } else {
+            program.write(buffer);
+        }
+    }
+
+    fn program_kill(&mut self, program_id: String, signal: u8) {
+        let child_pid = match self.programs.iter().find(|p| p.id == program_id) {
+            Some(program) if program.exit_status.is_none() => program.child_pid,
+            Some(_) => {
+                trace!("program {:?} has already exited", program_id);
+                return;
+            }
+            None => {
+                trace!("couldnt kill unknown program {:?}", program_id);
+                return;
+            }
+        };
+
+        info!("sending signal {} to program {:?}", signal, program_id);
+        if let Err(e) = kill_process_group(child_pid, signal as libc::c_int) {
+            warn!("couldnt signal program {:?}: {}", program_id, e);
+            return;
+        }
+
+        if signal as libc::c_int == libc::SIGTERM {
+            let tx = self.tx.clone();
+            let grace_period = self.config.kill_grace_period;
+            thread::spawn(move || {
+                thread::sleep(grace_period);
+                tx.send(ServerMsg::ProgramKillTimeout {
+                      program_id: program_id,
+                      child_pid: child_pid,
+                  })
+                  .ok();
+            });
+        }
+    }
+
+    fn program_kill_timeout(&mut self, program_id: String, child_pid: i32) {
+        let is_still_running = self.programs.iter().any(|p| {
+            p.id == program_id && p.child_pid == child_pid && p.exit_status.is_none()
+        });
+        if !is_still_running {
+            return;
+        }
+
+        warn!("program {:?} ignored SIGTERM, sending SIGKILL", program_id);
+        if let Err(e) = kill_process_group(child_pid, libc::SIGKILL) {
+            warn!("couldnt kill program {:?}: {}", program_id, e);
+        }
+    }
+
+    fn program_redraw_rect(&self, program_id: String, rect: Rect) {
+        if let Some(program) = self.programs.iter().find(|p| p.id == program_id) {
+            program.redraw_rect(rect);
+        } else {
+            trace!("couldnt redraw unknown program {:?}", program_id);
+        }
+    }
+
+    fn program_scrollback_fetch(&self,
+                                client_id: String,
+                                program_id: String,
+                                start: usize,
+                                count: usize) {
+        if let Some(program) = self.programs.iter().find(|p| p.id == program_id) {
+            program.fetch_scrollback(client_id, start, count);
+        } else {
+            trace!("couldnt fetch scrollback of unknown program {:?}", program_id);
+        }
+    }
+
+    fn send_msg_to_clients(&mut self, msg: ::client::ClientMsg) {
+        self.send_msg_to_clients_where(msg, |_| true);
+    }
+
+    /// Only clients subscribed to the program get msgs about what it draws
+    fn send_msg_to_subscribers(&mut self, program_id: &str, msg: ::client::ClientMsg) {
+        self.send_msg_to_clients_where(msg, |c| c.is_subscribed(program_id));
+    }
+
+    /// Clients that can't be sent to have gone away, so they are removed rather than waiting for
+    /// their ClientRemote msg.
+    fn send_msg_to_clients_where<F>(&mut self, msg: ::client::ClientMsg, f: F)
+        where F: Fn(&Client) -> bool
+    {
         // trace!("sending msg {:?} to {} clients", msg, self.clients.len());
-        for client in &self.clients {
-            let result = client.tx.send(msg.clone());
-            if hard {
-                result.expect("didnt send");
+        let mut gone: Vec<String> = vec![];
+        for client in self.clients.iter().filter(|c| f(c)) {
+            if let Err(e) = client.tx.send(msg.clone()) {
+                warn!("couldnt send to client {:?}: {}", client.id, e);
+                gone.push(client.id.clone());
             }
         }
+
+        for client_id in gone {
+            self.remove_client(client_id);
+        }
     }
 
-    fn start_program(&mut self, id: String, command_and_args: Vec<String>) {
-        // FIXME: get size from client
-        let size = Size::new(80, 24);
-        let (program, _) = Program::new(&id, &command_and_args, self.tx.clone(), size);
+    /// The env a program starts with, before the env it was asked for
+    fn program_env(&self, program_id: &str) -> Vec<String> {
+        // vterm understands 24 bit color, and the client passes it on or approximates it
+        let mut env = vec![format!("TERM={}", self.config.term),
+                           "COLORTERM=truecolor".to_string(),
+                           format!("INTERMIX={}", self.id),
+                           format!("INTERMIX_PROGRAM_ID={}", program_id)];
+        if let Some(ref path) = self.config.socket_path {
+            env.push(format!("INTERMIX_SOCKET={}", path.display()));
+        }
+        env
+    }
+
+    fn start_program(&mut self,
+                     id: String,
+                     mut command: Command,
+                     size: Size,
+                     monitor_silence: bool) {
+        let mut env = self.program_env(&id);
+        env.extend(command.env);
+        command.env = env;
+
+        info!("starting program {:?}: {:?}", id, command);
+        let (mut program, _) = Program::new(&id,
+                                            &command,
+                                            self.tx.clone(),
+                                            size.clone(),
+                                            self.config.scrollback_lines);
+        program.monitor_silence = monitor_silence;
         self.programs.push(program);
 
-        if let Some(client) = self.clients.first() {
-            client.tx
-                  .send(::client::ClientMsg::ProgramAdd {
-                      server_id: "some server".to_string(),
-                      program_id: id,
-                  })
-                  .unwrap();
+        self.send_msg_to_clients(::client::ClientMsg::ProgramAdd {
+            server_id: self.id.clone(),
+            program_id: id.clone(),
+            size: size,
+        });
+        if monitor_silence {
+            self.send_msg_to_clients(::client::ClientMsg::ProgramMonitorSilence {
+                server_id: self.id.clone(),
+                program_id: id,
+                is_on: true,
+            });
+        }
+    }
+
+    fn program_exited(&mut self, program_id: String, status: u8) {
+        info!("program {:?} exited with {}", program_id, status);
+        if let Some(program) = self.programs.iter_mut().find(|p| p.id == program_id) {
+            program.exit_status = Some(status);
+        } else {
+            warn!("unknown program {:?} exited", program_id);
+            return;
+        }
+
+        self.send_msg_to_clients(::client::ClientMsg::ProgramExited {
+            server_id: self.id.clone(),
+            program_id: program_id,
+            status: status,
+        });
+    }
+
+    /// Only exited programs can be removed. Running ones have to be killed first.
+    fn remove_program(&mut self, program_id: String) {
+        match self.programs.iter().position(|p| p.id == program_id) {
+            Some(i) if self.programs[i].exit_status.is_some() => {
+                info!("removing program {:?}", program_id);
+                self.programs.remove(i);
+            }
+            Some(_) => {
+                warn!("not removing program {:?} because it is still running", program_id);
+                return;
+            }
+            None => {
+                trace!("couldnt remove unknown program {:?}", program_id);
+                return;
+            }
+        }
+
+        self.send_msg_to_clients(::client::ClientMsg::ProgramRemove {
+            server_id: self.id.clone(),
+            program_id: program_id,
+        });
+    }
+
+    /// The new process gets the old one's id, so clients keep showing it in the same place.
+    fn restart_program(&mut self, program_id: String) {
+        let i = match self.programs.iter().position(|p| p.id == program_id) {
+            Some(i) => i,
+            None => {
+                trace!("couldnt restart unknown program {:?}", program_id);
+                return;
+            }
+        };
+        if self.programs[i].exit_status.is_none() {
+            warn!("not restarting program {:?} because it is still running", program_id);
+            return;
+        }
+
+        info!("restarting program {:?}", program_id);
+        let (mut program, _) = Program::new(&program_id,
+                                            &self.programs[i].command,
+                                            self.tx.clone(),
+                                            self.programs[i].size.clone(),
+                                            self.config.scrollback_lines);
+        program.monitor_silence = self.programs[i].monitor_silence;
+        // subscribers had the old screen, so they get all of the new one
+        program.redraw();
+        let had_title = {
+            let old = &self.programs[i];
+            !old.title.is_empty() || !old.icon_name.is_empty()
+        };
+        let had_cursor_style = self.programs[i].cursor_style != 0;
+        self.programs[i] = program;
+
+        self.send_msg_to_clients(::client::ClientMsg::ProgramUpdate {
+            server_id: self.id.clone(),
+            program_id: program_id.clone(),
+        });
+        if had_title {
+            self.send_msg_to_clients(::client::ClientMsg::ProgramTitle {
+                server_id: self.id.clone(),
+                program_id: program_id.clone(),
+                title: String::new(),
+                icon_name: String::new(),
+            });
+        }
+        if had_cursor_style {
+            self.send_msg_to_clients(::client::ClientMsg::ProgramCursorStyle {
+                server_id: self.id.clone(),
+                program_id: program_id,
+                style: 0,
+            });
         }
     }
+
+    /// Every client is told, since they all need to reflow their pane for the program
+    fn program_resize(&mut self, program_id: String, size: Size) {
+        if let Some(program) = self.programs.iter_mut().find(|p| p.id == program_id) {
+            if program.size == size {
+                return;
+            }
+
+            if let Err(e) = program.resize(size.clone()) {
+                warn!("couldnt resize program {:?}: {}", program_id, e);
+                return;
+            }
+        } else {
+            trace!("couldnt resize unknown program {:?}", program_id);
+            return;
+        }
+
+        self.send_msg_to_clients(::client::ClientMsg::ProgramResize {
+            server_id: self.id.clone(),
+            program_id: program_id,
+            size: size,
+        });
+    }
+}
+
+/// The bytes with every occurrence of the pattern taken out
+fn without(bytes: &[u8], pattern: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(pattern) {
+            i += pattern.len();
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    result
+}
+
+/// The child is a session leader, so its pid is also its process group id. Signalling the group
+/// gets anything it started too.
+fn kill_process_group(pid: i32, signal: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::kill(-pid, signal) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
 }
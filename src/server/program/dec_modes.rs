@@ -0,0 +1,140 @@
+/// Bracketed paste, where the terminal marks pasted text so the program can tell it wasn't typed
+pub const BRACKETED_PASTE: u16 = 2004;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    Private,
+}
+
+/// Follows the DEC private modes a program turns on and off with `ESC [ ? Pn h` and `ESC [ ? Pn l`.
+/// vterm acts on them but doesn't say which are on, and the server needs to know about some of
+/// them, like bracketed paste.
+///
+/// It only has to recognise those sequences, so everything else is skipped over. Sequences can be
+/// split across reads.
+pub struct DecModes {
+    enabled: Vec<u16>,
+    state: State,
+    params: Vec<u16>,
+    param: u16,
+}
+
+impl DecModes {
+    pub fn new() -> DecModes {
+        DecModes {
+            enabled: vec![],
+            state: State::Ground,
+            params: vec![],
+            param: 0,
+        }
+    }
+
+    pub fn is_enabled(&self, mode: u16) -> bool {
+        self.enabled.contains(&mode)
+    }
+
+    /// The modes that are on, in the order they were turned on
+    pub fn enabled(&self) -> &Vec<u16> {
+        &self.enabled
+    }
+
+    /// Returns true if any mode was turned on or off
+    pub fn scan(&mut self, bytes: &[u8]) -> bool {
+        let mut is_changed = false;
+
+        for byte in bytes {
+            self.state = match (self.state, *byte) {
+                (_, 0x1b) => State::Escape,
+                (State::Escape, b'[') => State::Csi,
+                (State::Csi, b'?') => {
+                    self.params.clear();
+                    self.param = 0;
+                    State::Private
+                }
+                (State::Private, b'0'...b'9') => {
+                    self.param = self.param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    State::Private
+                }
+                (State::Private, b';') => {
+                    self.params.push(self.param);
+                    self.param = 0;
+                    State::Private
+                }
+                (State::Private, b'h') => {
+                    is_changed |= self.set_params(true);
+                    State::Ground
+                }
+                (State::Private, b'l') => {
+                    is_changed |= self.set_params(false);
+                    State::Ground
+                }
+                _ => State::Ground,
+            };
+        }
+
+        is_changed
+    }
+
+    fn set_params(&mut self, is_enabled: bool) -> bool {
+        let mut is_changed = false;
+        self.params.push(self.param);
+
+        for mode in self.params.drain(..) {
+            let i = self.enabled.iter().position(|m| *m == mode);
+            match (i, is_enabled) {
+                (None, true) => self.enabled.push(mode),
+                (Some(i), false) => {
+                    self.enabled.remove(i);
+                }
+                _ => continue,
+            }
+            is_changed = true;
+        }
+
+        is_changed
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn it_follows_modes_being_turned_on_and_off() {
+        let mut modes = DecModes::new();
+
+        assert!(modes.scan(b"hello\x1b[?2004h"));
+        assert!(modes.is_enabled(BRACKETED_PASTE));
+
+        assert!(modes.scan(b"\x1b[?2004l"));
+        assert!(!modes.is_enabled(BRACKETED_PASTE));
+    }
+
+    #[test]
+    fn it_handles_several_modes_in_one_sequence() {
+        let mut modes = DecModes::new();
+        modes.scan(b"\x1b[?1049;2004h");
+
+        assert_eq!(*modes.enabled(), vec![1049, 2004]);
+    }
+
+    #[test]
+    fn it_handles_sequences_split_across_reads() {
+        let mut modes = DecModes::new();
+        assert!(!modes.scan(b"\x1b[?20"));
+        assert!(modes.scan(b"04h"));
+
+        assert!(modes.is_enabled(BRACKETED_PASTE));
+    }
+
+    #[test]
+    fn it_ignores_other_sequences() {
+        let mut modes = DecModes::new();
+        assert!(!modes.scan(b"\x1b[2004h\x1b[?2004m\x1b[31m"));
+
+        assert!(modes.enabled().is_empty());
+    }
+}
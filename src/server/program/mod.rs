@@ -1,18 +1,29 @@
 mod vte_worker;
 mod pty_reader;
+mod pty_writer;
+mod scrollback;
+mod dec_modes;
+mod osc;
+mod mouse;
 
 use vterm_sys::*;
+pub use self::dec_modes::BRACKETED_PASTE;
+pub use self::mouse::encode_mouse;
 use self::pty_reader::*;
+use self::pty_writer::*;
 use self::vte_worker::*;
-use std::ffi::CString;
-use std::fs::File;
+use std::env;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::{self, File};
 use std::io;
 use std::os::unix::prelude::*;
+use std::path::Path;
 use std::ptr;
 use std::sync::mpsc::*;
 use std::thread;
+use std::time::Instant;
 use super::*;
-use libc;
+use libc::{self, c_ushort};
 use pty;
 
 pub enum VteWorkerMsg {
@@ -22,71 +33,386 @@ pub enum VteWorkerMsg {
     PtyReadError,
     PtyReadZero,
 
-    #[allow(dead_code)]
     RequestRedrawRect {
         rect: Rect,
     },
+    Resize {
+        size: Size,
+    },
+    FetchScrollback {
+        client_id: String,
+        start: usize,
+        count: usize,
+    },
+    /// Sent after the last bytes from the pty, so the final screen is complete before anyone hears
+    /// about the exit.
+    ChildExited {
+        status: u8,
+    },
+    Quit,
+}
+
+/// What to run, and where. Kept with the program so it can be restarted the same way.
+#[derive(Clone, Debug)]
+pub struct Command {
+    pub command_and_args: Vec<String>,
+    pub cwd: Option<String>,
+    /// `NAME=value` sets a variable, a bare `NAME` unsets it
+    pub env: Vec<String>,
+}
+
+impl Command {
+    /// Runs the command line with the user's shell, so it can have pipes, globs and so on. With no
+    /// command line, the shell itself is run.
+    pub fn new(command_and_args: Vec<String>,
+               command_line: Option<String>,
+               cwd: Option<String>,
+               env: Vec<String>)
+               -> Command {
+        let command_and_args = match command_line {
+            Some(line) => vec![shell(), "-c".to_string(), line],
+            None if command_and_args.is_empty() => vec![shell()],
+            None => command_and_args,
+        };
+
+        Command {
+            command_and_args: command_and_args,
+            cwd: cwd,
+            env: env,
+        }
+    }
+}
+
+fn shell() -> String {
+    env::var("SHELL").unwrap_or("/bin/sh".to_string())
 }
 
 pub struct Program {
     pub child_pid: i32,
     pub id: String,
+    pub command: Command,
     pub size: Size,
     pub pty: File,
+    /// Set once the child has exited. The program's screen stays around until it is removed.
+    pub exit_status: Option<u8>,
+    /// The DEC private modes the program has turned on, as last heard from the vte worker
+    pub dec_modes: Vec<u16>,
+    /// What the program last set its title and icon name to, with OSC 0, 1 or 2
+    pub title: String,
+    pub icon_name: String,
+    /// When the program last wrote something, or None if it has been quiet for the server's
+    /// silence period
+    pub last_output: Option<Instant>,
+    /// Whether clients are told when the program goes silent
+    pub monitor_silence: bool,
+    /// The cursor style the program last set with DECSCUSR, 0 for the terminal's default
+    pub cursor_style: u8,
+    vte_tx: Sender<VteWorkerMsg>,
+    input_tx: Sender<Vec<u8>>,
 }
 
 impl Program {
     pub fn new(id: &str,
-               command_and_args: &Vec<String>,
+               command: &Command,
                server_tx: Sender<ServerMsg>,
-               size: Size)
+               size: Size,
+               scrollback_lines: usize)
                -> (Program, Vec<thread::JoinHandle<()>>) {
 
-        let child = fork(id, command_and_args);
+        let child = fork(id, command, &size);
 
         let mut threads = vec![];
 
-        let (vte_tx, handle) = VteWorker::spawn(server_tx.clone(), id);
+        let (vte_tx, handle) = VteWorker::spawn(server_tx.clone(),
+                                                id,
+                                                size.clone(),
+                                                scrollback_lines);
         threads.push(handle);
 
         let fd = child.pty().unwrap().as_raw_fd();
-        let io = unsafe { File::from_raw_fd(fd) };
-        let handle = PtyReader::spawn(io, vte_tx.clone(), id);
+        // The reader gets its own fd, so the two Files don't close the same one. Otherwise a
+        // restarted program could have its pty closed when the old one is dropped.
+        let io = unsafe { File::from_raw_fd(libc::dup(fd)) };
+        let handle = PtyReader::spawn(io, vte_tx.clone(), id, child.pid());
+        threads.push(handle);
+
+        let io = unsafe { File::from_raw_fd(libc::dup(fd)) };
+        let (input_tx, handle) = PtyWriter::spawn(io, id);
         threads.push(handle);
 
         let program = Program {
             child_pid: child.pid(),
             id: id.to_string(),
-            size: size, // todo: resize pty with this info
+            command: command.clone(),
+            size: size,
             pty: unsafe { File::from_raw_fd(fd) },
+            exit_status: None,
+            dec_modes: vec![],
+            title: String::new(),
+            icon_name: String::new(),
+            last_output: None,
+            monitor_silence: false,
+            cursor_style: 0,
+            vte_tx: vte_tx,
+            input_tx: input_tx,
         };
 
         (program, threads)
     }
+
+    /// Queue bytes to be written to the program's pty. It never blocks, even when the program
+    /// isn't reading.
+    pub fn write(&self, bytes: Vec<u8>) {
+        if self.input_tx.send(bytes).is_err() {
+            warn!("pty writer for program {} is gone", self.id);
+        }
+    }
+
+    /// Ask for the cells in the rect to be sent as damage, whether or not they've changed.
+    pub fn redraw_rect(&self, rect: Rect) {
+        if self.vte_tx.send(VteWorkerMsg::RequestRedrawRect { rect: rect }).is_err() {
+            warn!("vte worker for program {} is gone", self.id);
+        }
+    }
+
+    /// Ask for the whole screen to be sent as damage
+    pub fn redraw(&self) {
+        self.redraw_rect(Rect::new(Pos::new(0, 0), self.size.clone()));
+    }
+
+    /// The lines are sent to the client as ProgramScrollback
+    pub fn fetch_scrollback(&self, client_id: String, start: usize, count: usize) {
+        let msg = VteWorkerMsg::FetchScrollback {
+            client_id: client_id,
+            start: start,
+            count: count,
+        };
+        if self.vte_tx.send(msg).is_err() {
+            warn!("vte worker for program {} is gone", self.id);
+        }
+    }
+
+    /// Tell the program its screen is a new size. The kernel sends it SIGWINCH, and vterm reflows
+    /// to match.
+    pub fn resize(&mut self, size: Size) -> io::Result<()> {
+        try!(set_window_size(self.pty.as_raw_fd(), &size));
+        self.size = size.clone();
+
+        if self.vte_tx.send(VteWorkerMsg::Resize { size: size }).is_err() {
+            warn!("vte worker for program {} is gone", self.id);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        self.vte_tx.send(VteWorkerMsg::Quit).ok();
+    }
+}
+
+/// Block until the child exits, then return its status the way a shell reports it: the exit
+/// code, or 128 plus the number of the signal that killed it.
+pub fn wait_for_exit(pid: libc::pid_t) -> io::Result<u8> {
+    let mut status: libc::c_int = 0;
+    loop {
+        if unsafe { libc::waitpid(pid, &mut status, 0) } != -1 {
+            break;
+        }
+
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+
+    // these are macros in C
+    let signal = status & 0x7f;
+    if signal == 0 {
+        Ok(((status >> 8) & 0xff) as u8)
+    } else {
+        Ok((128 + signal) as u8)
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+struct WinSize {
+    rows: c_ushort,
+    cols: c_ushort,
+    x_pixels: c_ushort,
+    y_pixels: c_ushort,
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+const TIOCSWINSZ: libc::c_ulong = 0x80087467;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TIOCSWINSZ: libc::c_ulong = 0x5414;
+
+fn set_window_size(fd: RawFd, size: &Size) -> io::Result<()> {
+    let win_size = WinSize {
+        rows: size.height as c_ushort,
+        cols: size.width as c_ushort,
+        x_pixels: 0,
+        y_pixels: 0,
+    };
+
+    if unsafe { libc::ioctl(fd, TIOCSWINSZ, &win_size) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Everything the child needs between fork and exec, made beforehand. Only the forking thread
+/// makes it into the child, and locks other threads held at the time stay held there, so the
+/// child can't allocate, touch the environment or use stdout. It sticks to system calls.
+struct ChildSetup {
+    path: CString,
+    // only kept so the pointers stay good
+    #[allow(dead_code)]
+    argv: Vec<CString>,
+    #[allow(dead_code)]
+    envp: Vec<CString>,
+    argv_ptrs: Vec<*const libc::c_char>,
+    envp_ptrs: Vec<*const libc::c_char>,
+    cwd: Option<CString>,
+    cwd_error: CString,
+    exec_error: CString,
+    size_error: CString,
+}
+
+impl ChildSetup {
+    fn new(command: &Command) -> ChildSetup {
+        let vars = child_vars(command);
+        let name = command.command_and_args.first().cloned().unwrap_or(String::new());
+        let path = find_program(&name, &vars);
+
+        let argv: Vec<CString> = command.command_and_args
+                                        .iter()
+                                        .map(|s| to_cstring(s.clone().into_bytes()))
+                                        .collect();
+        let envp: Vec<CString> = vars.into_iter()
+                                     .map(|(name, value)| {
+                                         let mut bytes = name.into_vec();
+                                         bytes.push(b'=');
+                                         bytes.extend(value.into_vec());
+                                         to_cstring(bytes)
+                                     })
+                                     .collect();
+
+        // the pointers are into the CStrings' own buffers, which don't move with the Vecs
+        let mut argv_ptrs: Vec<*const libc::c_char> = argv.iter().map(|s| s.as_ptr()).collect();
+        argv_ptrs.push(ptr::null());
+        let mut envp_ptrs: Vec<*const libc::c_char> = envp.iter().map(|s| s.as_ptr()).collect();
+        envp_ptrs.push(ptr::null());
+
+        // The program is still run if the directory is missing, which it may well be for a
+        // client on another machine. The error shows in its pane.
+        let cwd = command.cwd.as_ref();
+        ChildSetup {
+            path: path,
+            argv: argv,
+            envp: envp,
+            argv_ptrs: argv_ptrs,
+            envp_ptrs: envp_ptrs,
+            cwd: cwd.map(|cwd| to_cstring(cwd.clone().into_bytes())),
+            cwd_error: to_cstring(format!("intermix: couldn't change directory to {}\n",
+                                          cwd.map_or("", |cwd| &cwd[..]))
+                                      .into_bytes()),
+            exec_error: to_cstring(format!("intermix: couldn't run {}\n", name).into_bytes()),
+            size_error: to_cstring(b"intermix: couldn't size the pty\n".to_vec()),
+        }
+    }
+
+    /// Only called in the child. Doesn't return.
+    fn exec(&self, size: &Size) -> ! {
+        // set the size before exec so the program never sees the pty's default size
+        if set_window_size(libc::STDIN_FILENO, size).is_err() {
+            child_exit(&self.size_error, 1);
+        }
+
+        if let Some(ref cwd) = self.cwd {
+            if unsafe { libc::chdir(cwd.as_ptr()) } == -1 {
+                child_write(&self.cwd_error);
+            }
+        }
+
+        unsafe {
+            libc::execve(self.path.as_ptr(),
+                         self.argv_ptrs.as_ptr(),
+                         self.envp_ptrs.as_ptr())
+        };
+        child_exit(&self.exec_error, 127);
+    }
+}
+
+/// The server's environment with the command's changes made to it
+fn child_vars(command: &Command) -> Vec<(OsString, OsString)> {
+    let mut vars: Vec<(OsString, OsString)> = env::vars_os().collect();
+    for var in &command.env {
+        let (name, value) = match var.find('=') {
+            Some(i) => (&var[..i], Some(&var[i + 1..])),
+            None => (&var[..], None),
+        };
+        vars.retain(|v| v.0.as_os_str() != OsStr::new(name));
+        if let Some(value) = value {
+            vars.push((OsString::from(name), OsString::from(value)));
+        }
+    }
+    vars
+}
+
+/// execve doesn't search PATH like execvp does, so it's done here, with the PATH the child gets.
+/// Names with a slash in them are used as they are.
+fn find_program(name: &str, vars: &[(OsString, OsString)]) -> CString {
+    if !name.contains('/') {
+        let dirs = vars.iter()
+                       .find(|v| v.0.as_os_str() == OsStr::new("PATH"))
+                       .map_or(OsString::from("/usr/bin:/bin"), |v| v.1.clone());
+        for dir in env::split_paths(&dirs).filter(|d| d.is_absolute()) {
+            let candidate = dir.join(name);
+            if is_executable(&candidate) {
+                return to_cstring(candidate.into_os_string().into_vec());
+            }
+        }
+    }
+    to_cstring(name.to_string().into_bytes())
+}
+
+fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+/// Args and env vars can't have nul bytes in them, so anything after one is dropped
+fn to_cstring(mut bytes: Vec<u8>) -> CString {
+    if let Some(i) = bytes.iter().position(|b| *b == 0) {
+        bytes.truncate(i);
+    }
+    CString::new(bytes).unwrap()
+}
+
+/// Writes to the pty, where the program's output would go
+fn child_write(msg: &CString) {
+    let bytes = msg.as_bytes();
+    unsafe { libc::write(libc::STDOUT_FILENO, bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+}
+
+fn child_exit(msg: &CString, status: libc::c_int) -> ! {
+    child_write(msg);
+    unsafe { libc::_exit(status) }
 }
 
-fn fork(id: &str, command_and_args: &Vec<String>) -> pty::Child {
+fn fork(id: &str, command: &Command, size: &Size) -> pty::Child {
     info!("forking program {}", id);
 
+    let setup = ChildSetup::new(command);
     match pty::fork() {
         Ok(child) => {
             if child.pid() == 0 {
-                let cstrings: Vec<CString> = command_and_args.iter()
-                                                             .map(|s| {
-                                                                 let bytes = s.clone().into_bytes();
-                                                                 CString::new(bytes).unwrap()
-                                                             })
-                                                             .collect();
-
-                let mut ptrs: Vec<*const libc::c_char> = (&cstrings)
-                                                             .iter()
-                                                             .map(|s| s.as_ptr())
-                                                             .collect();
-
-                ptrs.push(ptr::null());
-
-                let ret = unsafe { libc::execvp(*ptrs.as_ptr(), ptrs.as_mut_ptr()) };
-                panic!("error {} in execvp {}", ret, io::Error::last_os_error());
+                setup.exec(size);
             } else {
                 child
             }
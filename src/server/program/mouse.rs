@@ -0,0 +1,142 @@
+use vterm_sys::Pos;
+
+/// Only button presses, with no modifiers
+pub const MOUSE_X10: u16 = 9;
+/// Presses and releases
+pub const MOUSE_NORMAL: u16 = 1000;
+/// Presses, releases, and motion while a button is held
+pub const MOUSE_BUTTON_EVENT: u16 = 1002;
+/// Presses, releases, and all motion
+pub const MOUSE_ANY_EVENT: u16 = 1003;
+/// Report with `ESC [ < b ; x ; y M`, which has no limit on the position and says which button
+/// was released
+pub const MOUSE_SGR: u16 = 1006;
+
+/// Bits of the button byte
+const MODIFIERS: u8 = 4 | 8 | 16;
+const MOTION: u8 = 32;
+const NO_BUTTON: u8 = 3;
+
+/// Legacy reports put the button and each coordinate in a byte, offset by 32
+const MAX_LEGACY_COORD: usize = 255 - 32 - 1;
+const MAX_LEGACY_BUTTON: u8 = 255 - 32;
+
+/// Encodes a mouse event the way the program asked for with its DEC private modes, or gives None
+/// if it didn't ask for this kind of event. button is an SGR button byte, pos is 0 based.
+///
+/// The utf8 (1005) and urxvt (1015) encodings aren't supported, programs that ask for them get
+/// the legacy one.
+pub fn encode_mouse(modes: &[u16], button: u8, pos: &Pos, is_release: bool) -> Option<Vec<u8>> {
+    let is_motion = button & MOTION != 0;
+
+    // the most eventful one wins if a program turned on several
+    let tracking = [MOUSE_ANY_EVENT, MOUSE_BUTTON_EVENT, MOUSE_NORMAL, MOUSE_X10];
+    let mode = match tracking.iter().find(|m| modes.contains(*m)) {
+        Some(mode) => *mode,
+        None => return None,
+    };
+
+    let is_reported = match mode {
+        MOUSE_ANY_EVENT => true,
+        MOUSE_BUTTON_EVENT => !is_motion || button & 3 != NO_BUTTON,
+        MOUSE_NORMAL => !is_motion,
+        _ => !is_motion && !is_release,
+    };
+    if !is_reported {
+        return None;
+    }
+
+    let button = if mode == MOUSE_X10 {
+        button & !MODIFIERS
+    } else {
+        button
+    };
+
+    if modes.contains(&MOUSE_SGR) {
+        let end = if is_release { 'm' } else { 'M' };
+        return Some(format!("\x1b[<{};{};{}{}", button, pos.x + 1, pos.y + 1, end).into_bytes());
+    }
+
+    if pos.x > MAX_LEGACY_COORD || pos.y > MAX_LEGACY_COORD {
+        return None;
+    }
+    // legacy releases don't say which button it was
+    let button = if is_release {
+        button & (MODIFIERS | MOTION) | NO_BUTTON
+    } else {
+        button
+    };
+    if button > MAX_LEGACY_BUTTON {
+        return None;
+    }
+
+    let mut bytes = b"\x1b[M".to_vec();
+    bytes.push(32 + button);
+    bytes.push((32 + pos.x + 1) as u8);
+    bytes.push((32 + pos.y + 1) as u8);
+    Some(bytes)
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use vterm_sys::Pos;
+
+    #[test]
+    fn it_reports_nothing_without_a_mouse_mode() {
+        assert_eq!(encode_mouse(&[2004], 0, &Pos::new(1, 2), false), None);
+    }
+
+    #[test]
+    fn it_encodes_sgr_presses_and_releases() {
+        let modes = [MOUSE_NORMAL, MOUSE_SGR];
+
+        assert_eq!(encode_mouse(&modes, 0, &Pos::new(300, 2), false),
+                   Some(b"\x1b[<0;301;3M".to_vec()));
+        assert_eq!(encode_mouse(&modes, 2, &Pos::new(0, 0), true),
+                   Some(b"\x1b[<2;1;1m".to_vec()));
+    }
+
+    #[test]
+    fn it_encodes_legacy_presses_and_releases() {
+        let modes = [MOUSE_NORMAL];
+
+        assert_eq!(encode_mouse(&modes, 0, &Pos::new(1, 2), false),
+                   Some(b"\x1b[M\x20\x22\x23".to_vec()));
+        assert_eq!(encode_mouse(&modes, 16 + 1, &Pos::new(1, 2), true),
+                   Some(b"\x1b[M\x33\x22\x23".to_vec()));
+        assert_eq!(encode_mouse(&modes, 0, &Pos::new(223, 2), false), None);
+    }
+
+    #[test]
+    fn it_drops_legacy_buttons_that_dont_fit_in_a_byte() {
+        let modes = [MOUSE_ANY_EVENT];
+
+        assert_eq!(encode_mouse(&modes, 223, &Pos::new(0, 0), false),
+                   Some(b"\x1b[M\xff\x21\x21".to_vec()));
+        assert_eq!(encode_mouse(&modes, 224, &Pos::new(0, 0), false), None);
+        assert_eq!(encode_mouse(&[MOUSE_ANY_EVENT, MOUSE_SGR], 224, &Pos::new(0, 0), false),
+                   Some(b"\x1b[<224;1;1M".to_vec()));
+    }
+
+    #[test]
+    fn it_reports_motion_only_when_asked() {
+        let drag = MOTION;
+        let hover = MOTION | NO_BUTTON;
+
+        assert_eq!(encode_mouse(&[MOUSE_NORMAL, MOUSE_SGR], drag, &Pos::new(0, 0), false), None);
+        assert!(encode_mouse(&[MOUSE_BUTTON_EVENT, MOUSE_SGR], drag, &Pos::new(0, 0), false)
+                    .is_some());
+        assert_eq!(encode_mouse(&[MOUSE_BUTTON_EVENT, MOUSE_SGR], hover, &Pos::new(0, 0), false),
+                   None);
+        assert!(encode_mouse(&[MOUSE_ANY_EVENT, MOUSE_SGR], hover, &Pos::new(0, 0), false)
+                    .is_some());
+    }
+
+    #[test]
+    fn it_sends_x10_presses_without_modifiers() {
+        assert_eq!(encode_mouse(&[MOUSE_X10], 8 + 1, &Pos::new(0, 0), false),
+                   Some(b"\x1b[M\x21\x21\x21".to_vec()));
+        assert_eq!(encode_mouse(&[MOUSE_X10], 1, &Pos::new(0, 0), true), None);
+    }
+}
@@ -0,0 +1,158 @@
+use rustc_serialize::base64::FromBase64;
+
+/// OSC strings longer than this are dropped rather than kept growing
+const MAX_OSC_BYTES: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+/// Picks the operating system commands, `ESC ] ... BEL` or `ESC ] ... ESC \`, out of what a
+/// program writes. vterm doesn't pass them on, and some of them, like setting the clipboard,
+/// are for the server to act on.
+///
+/// Like DecModes it skips everything else, and strings can be split across reads.
+pub struct OscScanner {
+    state: State,
+    string: Vec<u8>,
+    is_too_long: bool,
+}
+
+impl OscScanner {
+    pub fn new() -> OscScanner {
+        OscScanner {
+            state: State::Ground,
+            string: vec![],
+            is_too_long: false,
+        }
+    }
+
+    /// Returns the strings that were finished in these bytes, without their ESC ] and terminator
+    pub fn scan(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut strings = vec![];
+
+        for byte in bytes {
+            self.state = match (self.state, *byte) {
+                (State::Osc, 0x07) | (State::OscEscape, b'\\') => {
+                    if !self.is_too_long {
+                        strings.push(self.string.clone());
+                    }
+                    State::Ground
+                }
+                (State::Osc, 0x1b) => State::OscEscape,
+                (State::Osc, _) => {
+                    if self.string.len() < MAX_OSC_BYTES {
+                        self.string.push(*byte);
+                    } else {
+                        self.is_too_long = true;
+                    }
+                    State::Osc
+                }
+                (_, 0x1b) => State::Escape,
+                (State::Escape, b']') | (State::OscEscape, b']') => {
+                    self.string.clear();
+                    self.is_too_long = false;
+                    State::Osc
+                }
+                _ => State::Ground,
+            };
+        }
+
+        strings
+    }
+}
+
+/// Splits an OSC string into its number and the text after the first `;`
+pub fn parse_osc(string: &[u8]) -> Option<(u32, &[u8])> {
+    let i = match string.iter().position(|b| *b == b';') {
+        Some(i) => i,
+        None => return None,
+    };
+    if i == 0 || !string[..i].iter().all(|b| b'0' <= *b && *b <= b'9') {
+        return None;
+    }
+
+    let number = string[..i].iter().fold(0u32, |n, b| {
+        n.saturating_mul(10).saturating_add((b - b'0') as u32)
+    });
+    Some((number, &string[i + 1..]))
+}
+
+/// The text of an OSC 52 is `Pc;Pd`, which selections to set and their base64 contents. A `?`
+/// for the contents asks for the clipboard back, which isn't supported, so that gives None.
+pub fn parse_clipboard(text: &[u8]) -> Option<Vec<u8>> {
+    let data = match text.iter().position(|b| *b == b';') {
+        Some(i) => &text[i + 1..],
+        None => return None,
+    };
+    if data == b"?" {
+        return None;
+    }
+
+    data.from_base64().ok()
+}
+
+/// The text of an OSC 0, 1 or 2 is the title or icon name. Control characters are taken out so it
+/// can be drawn as it is.
+pub fn parse_title(text: &[u8]) -> String {
+    String::from_utf8_lossy(text).chars().filter(|c| !c.is_control()).collect()
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn it_finds_strings_ended_by_bel_or_st() {
+        let mut scanner = OscScanner::new();
+
+        let strings = scanner.scan(b"a\x1b]2;title\x07b\x1b]52;c;aGk=\x1b\\c");
+
+        assert_eq!(strings, vec![b"2;title".to_vec(), b"52;c;aGk=".to_vec()]);
+    }
+
+    #[test]
+    fn it_handles_strings_split_across_reads() {
+        let mut scanner = OscScanner::new();
+
+        assert!(scanner.scan(b"\x1b]52;c;aG").is_empty());
+        assert!(scanner.scan(b"k=\x1b").is_empty());
+        assert_eq!(scanner.scan(b"\\"), vec![b"52;c;aGk=".to_vec()]);
+    }
+
+    #[test]
+    fn it_drops_strings_that_are_too_long() {
+        let mut scanner = OscScanner::new();
+        scanner.scan(b"\x1b]52;c;");
+
+        assert!(scanner.scan(&vec![b'a'; MAX_OSC_BYTES]).is_empty());
+        assert!(scanner.scan(b"\x07").is_empty());
+        assert_eq!(scanner.scan(b"\x1b]0;x\x07"), vec![b"0;x".to_vec()]);
+    }
+
+    #[test]
+    fn it_parses_the_number() {
+        assert_eq!(parse_osc(b"52;c;aGk="), Some((52, &b"c;aGk="[..])));
+        assert_eq!(parse_osc(b"2;"), Some((2, &b""[..])));
+        assert_eq!(parse_osc(b"x;y"), None);
+        assert_eq!(parse_osc(b"52"), None);
+    }
+
+    #[test]
+    fn it_decodes_clipboard_contents() {
+        assert_eq!(parse_clipboard(b"c;aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(parse_clipboard(b"pc;"), Some(vec![]));
+        assert_eq!(parse_clipboard(b"c;?"), None);
+        assert_eq!(parse_clipboard(b"c;!!"), None);
+    }
+
+    #[test]
+    fn it_takes_control_characters_out_of_titles() {
+        assert_eq!(parse_title("vim ~/λ".as_bytes()), "vim ~/λ".to_string());
+        assert_eq!(parse_title(b"a\x1b[31mb\tc"), "a[31mbc".to_string());
+    }
+}
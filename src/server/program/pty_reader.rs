@@ -10,8 +10,8 @@ use super::*;
 ///
 /// An alternative would be to have 1 thread run for all programs using mio.
 pub struct PtyReader {
-    #[allow(dead_code)]
     program_id: String,
+    child_pid: i32,
     pty: File,
     vte_tx: mpsc::Sender<VteWorkerMsg>,
 }
@@ -19,22 +19,41 @@ pub struct PtyReader {
 impl PtyReader {
     pub fn spawn(io: File,
                  vte_tx: mpsc::Sender<VteWorkerMsg>,
-                 program_id: &str)
+                 program_id: &str,
+                 child_pid: i32)
                  -> thread::JoinHandle<()> {
         let program_id = program_id.to_string();
         info!("spawning pty reader for program {}", program_id);
         thread::spawn(move || {
-            let mut reader = PtyReader::new(io, vte_tx, &program_id);
+            let mut reader = PtyReader::new(io, vte_tx, &program_id, child_pid);
             reader.enter_listen_loop();
+            reader.reap_child();
             info!("exiting pty reader for program {}", program_id);
         })
     }
 
-    fn new(io: File, vte_tx: mpsc::Sender<VteWorkerMsg>, program_id: &str) -> PtyReader {
+    fn new(io: File,
+           vte_tx: mpsc::Sender<VteWorkerMsg>,
+           program_id: &str,
+           child_pid: i32)
+           -> PtyReader {
         PtyReader {
             pty: io,
             vte_tx: vte_tx,
             program_id: program_id.to_string(),
+            child_pid: child_pid,
+        }
+    }
+
+    /// The pty closes when the child exits, so this is the thread that waits for it. The child
+    /// would be a zombie otherwise.
+    fn reap_child(&mut self) {
+        match wait_for_exit(self.child_pid) {
+            Ok(status) => {
+                info!("program {} exited with {}", self.program_id, status);
+                self.vte_tx.send(VteWorkerMsg::ChildExited { status: status }).ok();
+            }
+            Err(e) => error!("couldnt wait for program {}: {}", self.program_id, e),
         }
     }
 
@@ -47,15 +66,16 @@ impl PtyReader {
             let bytes = match reader.read(&mut buf) {
                 Ok(num_bytes) => {
                     if num_bytes == 0 {
-                        self.vte_tx.send(VteWorkerMsg::PtyReadZero).unwrap();
-                        error!("zero bytes reading from pty");
+                        self.vte_tx.send(VteWorkerMsg::PtyReadZero).ok();
+                        info!("zero bytes reading from pty");
                         break;
                     }
                     &buf[0..num_bytes]
                 }
                 Err(e) => {
-                    self.vte_tx.send(VteWorkerMsg::PtyReadError).unwrap();
-                    error!("error reading from pty: {}", e.description());
+                    // linux gives EIO once the child has exited
+                    self.vte_tx.send(VteWorkerMsg::PtyReadError).ok();
+                    info!("error reading from pty: {}", e.description());
                     break;
                 }
             };
@@ -63,7 +83,9 @@ impl PtyReader {
             let mut bytes_vec: Vec<u8> = vec![];
             bytes_vec.extend(bytes);
             let msg = VteWorkerMsg::PtyRead { bytes: bytes_vec };
-            self.vte_tx.send(msg).unwrap();
+            if self.vte_tx.send(msg).is_err() {
+                break;
+            }
         }
     }
 }
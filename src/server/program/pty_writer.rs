@@ -0,0 +1,43 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::sync::mpsc;
+use std::thread;
+
+/// A worker that writes input to a program's pty.
+///
+/// Writing blocks once the pty's buffer is full, which happens when a program stops reading. It
+/// only holds up this thread, rather than the server.
+pub struct PtyWriter {
+    program_id: String,
+    pty: File,
+    rx: mpsc::Receiver<Vec<u8>>,
+}
+
+impl PtyWriter {
+    /// Bytes sent on the returned channel are written in order. The thread exits once the
+    /// channel is dropped or the pty can't be written to.
+    pub fn spawn(io: File, program_id: &str) -> (mpsc::Sender<Vec<u8>>, thread::JoinHandle<()>) {
+        let program_id = program_id.to_string();
+        let (tx, rx) = mpsc::channel();
+        info!("spawning pty writer for program {}", program_id);
+        let handle = thread::spawn(move || {
+            let mut writer = PtyWriter {
+                program_id: program_id,
+                pty: io,
+                rx: rx,
+            };
+            writer.enter_listen_loop();
+            info!("exiting pty writer for program {}", writer.program_id);
+        });
+        (tx, handle)
+    }
+
+    fn enter_listen_loop(&mut self) {
+        while let Ok(bytes) = self.rx.recv() {
+            if let Err(e) = self.pty.write_all(&bytes) {
+                warn!("couldnt write to program {}: {}", self.program_id, e);
+                break;
+            }
+        }
+    }
+}
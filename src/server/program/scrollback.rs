@@ -0,0 +1,99 @@
+use std::cmp;
+use std::collections::VecDeque;
+use vterm_sys::ScreenCell;
+
+/// Lines that have scrolled off the top of a program's screen, newest at the back. Once it is full
+/// the oldest lines are dropped to make room.
+pub struct Scrollback {
+    lines: VecDeque<Vec<ScreenCell>>,
+    max_lines: usize,
+}
+
+impl Scrollback {
+    pub fn new(max_lines: usize) -> Scrollback {
+        Scrollback {
+            lines: VecDeque::new(),
+            max_lines: max_lines,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn push(&mut self, line: Vec<ScreenCell>) {
+        if self.max_lines == 0 {
+            return;
+        }
+        if self.lines.len() == self.max_lines {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Drops the newest line, when vterm's screen grows taller and it asks for one back. vterm-sys
+    /// can't give vterm the cells, so the screen gets a blank line instead.
+    pub fn pop(&mut self) -> Option<Vec<ScreenCell>> {
+        self.lines.pop_back()
+    }
+
+    /// Lines are counted back from the screen, so 0 is the line just above it. They are returned
+    /// oldest first, the way they were shown. Lines that don't exist are left out.
+    pub fn lines(&self, start: usize, count: usize) -> Vec<Vec<ScreenCell>> {
+        let len = self.lines.len();
+        let end = len - cmp::min(start, len);
+        let begin = end - cmp::min(count, end);
+
+        self.lines.iter().skip(begin).take(end - begin).cloned().collect()
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+    use vterm_sys::ScreenCell;
+
+    fn line(ch: u8) -> Vec<ScreenCell> {
+        let mut cell: ScreenCell = Default::default();
+        cell.chars = vec![ch];
+        vec![cell]
+    }
+
+    fn chars(lines: Vec<Vec<ScreenCell>>) -> Vec<u8> {
+        lines.iter().map(|l| l[0].chars[0]).collect()
+    }
+
+    #[test]
+    fn it_drops_the_oldest_lines_when_full() {
+        let mut scrollback = Scrollback::new(2);
+        scrollback.push(line(b'a'));
+        scrollback.push(line(b'b'));
+        scrollback.push(line(b'c'));
+
+        assert_eq!(scrollback.len(), 2);
+        assert_eq!(chars(scrollback.lines(0, 10)), b"bc".to_vec());
+    }
+
+    #[test]
+    fn it_pops_the_newest_line() {
+        let mut scrollback = Scrollback::new(10);
+        scrollback.push(line(b'a'));
+        scrollback.push(line(b'b'));
+
+        assert_eq!(chars(vec![scrollback.pop().unwrap()]), b"b".to_vec());
+        assert_eq!(scrollback.len(), 1);
+    }
+
+    #[test]
+    fn it_counts_lines_back_from_the_screen() {
+        let mut scrollback = Scrollback::new(10);
+        for ch in b"abcde".iter() {
+            scrollback.push(line(*ch));
+        }
+
+        assert_eq!(chars(scrollback.lines(0, 2)), b"de".to_vec());
+        assert_eq!(chars(scrollback.lines(1, 3)), b"bcd".to_vec());
+        assert_eq!(chars(scrollback.lines(3, 10)), b"ab".to_vec());
+        assert!(scrollback.lines(5, 1).is_empty());
+    }
+}
@@ -3,6 +3,9 @@ use std::sync::mpsc::*;
 use std::thread;
 use vterm_sys::*;
 use super::*;
+use super::dec_modes::DecModes;
+use super::osc::*;
+use super::scrollback::Scrollback;
 
 /// Runs bytes from the pty through VTerm, and sends ServerMsgs.
 ///
@@ -16,11 +19,22 @@ pub struct VteWorker {
     server_tx: Sender<::server::ServerMsg>,
     program_id: String,
     vterm: VTerm,
+    cursor_pos: Pos,
+    /// How the program last set the cursor to look, with vterm's numbering for the shape. The
+    /// shape is 0 until the program sets it, so the terminal's default is used until then.
+    cursor_shape: u8,
+    is_cursor_blinking: bool,
+    is_cursor_visible: bool,
+    scrollback: Scrollback,
+    dec_modes: DecModes,
+    osc_scanner: OscScanner,
 }
 
 impl VteWorker {
     pub fn spawn(server_tx: Sender<::server::ServerMsg>,
-                 program_id: &str)
+                 program_id: &str,
+                 size: Size,
+                 scrollback_lines: usize)
                  -> (Sender<VteWorkerMsg>, thread::JoinHandle<()>) {
         let (tx, rx) = channel::<VteWorkerMsg>();
         let tx_clone = tx.clone();
@@ -28,7 +42,7 @@ impl VteWorker {
 
         info!("spawning vte worker for program {}", program_id);
         let handle = thread::spawn(move || {
-            let mut worker = VteWorker::new(server_tx, tx, rx, &program_id);
+            let mut worker = VteWorker::new(server_tx, tx, rx, &program_id, size, scrollback_lines);
             worker.enter_listen_loop();
             info!("exiting vte worker for program {}", program_id);
         });
@@ -39,11 +53,12 @@ impl VteWorker {
     pub fn new(server_tx: Sender<::server::ServerMsg>,
                tx: Sender<VteWorkerMsg>,
                rx: Receiver<VteWorkerMsg>,
-               program_id: &str)
+               program_id: &str,
+               size: Size,
+               scrollback_lines: usize)
                -> VteWorker {
 
-        // FIXME: get size from self
-        let mut vterm = VTerm::new(&Size::new(80, 24));
+        let mut vterm = VTerm::new(&size);
         let fg = vterm.state_get_rgb_color_from_palette(7);
         let bg = vterm.state_get_rgb_color_from_palette(0);
         vterm.state_set_default_colors(&fg, &bg);
@@ -56,6 +71,13 @@ impl VteWorker {
             server_tx: server_tx,
             tx: tx,
             vterm: vterm,
+            cursor_pos: Pos::new(0, 0),
+            cursor_shape: 0,
+            is_cursor_blinking: true,
+            is_cursor_visible: true,
+            scrollback: Scrollback::new(scrollback_lines),
+            dec_modes: DecModes::new(),
+            osc_scanner: OscScanner::new(),
         }
     }
 
@@ -71,7 +93,12 @@ impl VteWorker {
 
         loop {
             select! {
-                program_event = program_event_rx.recv() => self.handle_program_event(program_event.unwrap()),
+                program_event = program_event_rx.recv() => {
+                    match program_event {
+                        Ok(VteWorkerMsg::Quit) | Err(_) => break,
+                        Ok(event) => self.handle_program_event(event),
+                    }
+                },
                 screen_event = vterm_event_rx.recv() => self.handle_screen_event(screen_event.unwrap())
             }
         }
@@ -79,7 +106,12 @@ impl VteWorker {
 
     fn handle_screen_event(&mut self, event: ScreenEvent) {
         match event {
-            ScreenEvent::Bell => info!("Bell"),
+            ScreenEvent::Bell => {
+                let event = ::server::ServerMsg::ProgramBell {
+                    program_id: self.program_id.clone(),
+                };
+                self.server_tx.send(event).unwrap();
+            }
             ScreenEvent::Damage(e) => {
                 info!("Damage: {:?}", e);
                 let event = ::server::ServerMsg::ProgramDamage {
@@ -91,11 +123,12 @@ impl VteWorker {
             }
             ScreenEvent::MoveCursor(e) => {
                 info!("MoveCursor: {:?}", e);
+                self.cursor_pos = e.new.clone();
                 let event = ::server::ServerMsg::ProgramMoveCursor {
                     program_id: self.program_id.clone(),
                     new: e.new,
                     old: e.old,
-                    is_visible: e.is_visible,
+                    is_visible: self.is_cursor_visible,
                 };
                 self.server_tx.send(event).unwrap();
             }
@@ -128,28 +161,179 @@ impl VteWorker {
                 }
             }
             ScreenEvent::Resize(e) => info!("Resize: {:?}", e),
-            ScreenEvent::SbPopLine(_) => info!("SbPopLine"),
-            ScreenEvent::SbPushLine(_) => info!("SbPushLine"),
+            ScreenEvent::SbPopLine(_) => {
+                trace!("SbPopLine");
+                // the line isn't restored, vterm fills its place with blank cells
+                self.scrollback.pop();
+            }
+            ScreenEvent::SbPushLine(e) => {
+                trace!("SbPushLine");
+                self.scrollback.push(e.cells);
+            }
             ScreenEvent::AltScreen(e) => info!("AltScreen: {:?}", e),
-            ScreenEvent::CursorBlink(e) => info!("CursorBlink: {:?}", e),
-            ScreenEvent::CursorShape(e) => info!("CursorShape: {:?}", e),
-            ScreenEvent::CursorVisible(e) => info!("CursorVisible: {:?}", e),
-            ScreenEvent::IconName(e) => info!("IconName: {:?}", e),
-            ScreenEvent::Mouse(e) => info!("Mouse: {:?}", e),
+            ScreenEvent::CursorBlink(e) => {
+                trace!("CursorBlink: {:?}", e);
+                let style = self.cursor_style();
+                self.is_cursor_blinking = e.is_true;
+                self.send_cursor_style_if_changed(style);
+            }
+            ScreenEvent::CursorShape(e) => {
+                trace!("CursorShape: {:?}", e);
+                let style = self.cursor_style();
+                self.cursor_shape = e.value as u8;
+                self.send_cursor_style_if_changed(style);
+            }
+            ScreenEvent::CursorVisible(e) => {
+                trace!("CursorVisible: {:?}", e);
+                if e.is_true != self.is_cursor_visible {
+                    self.is_cursor_visible = e.is_true;
+                    let event = ::server::ServerMsg::ProgramMoveCursor {
+                        program_id: self.program_id.clone(),
+                        new: self.cursor_pos.clone(),
+                        old: self.cursor_pos.clone(),
+                        is_visible: self.is_cursor_visible,
+                    };
+                    self.server_tx.send(event).unwrap();
+                }
+            }
+            // titles and icon names are picked out of the pty bytes with the osc scanner
+            ScreenEvent::IconName(e) => trace!("IconName: {:?}", e),
+            // the mouse modes are followed by dec_modes, which knows the encoding too
+            ScreenEvent::Mouse(e) => trace!("Mouse: {:?}", e),
             ScreenEvent::Reverse(e) => info!("Reverse: {:?}", e),
-            ScreenEvent::Title(e) => info!("Title: {:?}", e),
+            ScreenEvent::Title(e) => trace!("Title: {:?}", e),
+        }
+    }
+
+    /// The cursor style with DECSCUSR's numbering: 0 is the terminal's default, 1 and 2 a
+    /// blinking and steady block, 3 and 4 an underline, and 5 and 6 a bar. vterm's shapes are
+    /// block, underline and bar from 1.
+    fn cursor_style(&self) -> u8 {
+        if self.cursor_shape == 0 {
+            return 0;
+        }
+        let steady = if self.is_cursor_blinking { 0 } else { 1 };
+        self.cursor_shape * 2 - 1 + steady
+    }
+
+    fn send_cursor_style_if_changed(&self, old_style: u8) {
+        let style = self.cursor_style();
+        if style != old_style {
+            let event = ::server::ServerMsg::ProgramCursorStyle {
+                program_id: self.program_id.clone(),
+                style: style,
+            };
+            self.server_tx.send(event).unwrap();
+        }
+    }
+
+    fn handle_osc(&self, string: &[u8]) {
+        match parse_osc(string) {
+            Some((52, text)) => {
+                if let Some(bytes) = parse_clipboard(text) {
+                    let event = ::server::ServerMsg::ProgramClipboard {
+                        program_id: self.program_id.clone(),
+                        bytes: bytes,
+                    };
+                    self.server_tx.send(event).unwrap();
+                } else {
+                    trace!("ignoring clipboard request {:?}", String::from_utf8_lossy(text));
+                }
+            }
+            Some((n @ 0...2, text)) => {
+                let title = parse_title(text);
+                let event = ::server::ServerMsg::ProgramTitle {
+                    program_id: self.program_id.clone(),
+                    title: if n != 1 { Some(title.clone()) } else { None },
+                    icon_name: if n != 2 { Some(title) } else { None },
+                };
+                self.server_tx.send(event).unwrap();
+            }
+            _ => trace!("ignoring osc {:?}", String::from_utf8_lossy(string)),
         }
     }
 
     fn handle_program_event(&mut self, event: VteWorkerMsg) {
         match event {
             VteWorkerMsg::PtyRead{bytes} => {
+                let event = ::server::ServerMsg::ProgramOutput {
+                    program_id: self.program_id.clone(),
+                };
+                self.server_tx.send(event).unwrap();
+
                 self.vterm.write(bytes.as_slice()).unwrap();
                 self.vterm.screen_flush_damage();
+
+                if self.dec_modes.scan(&bytes) {
+                    let event = ::server::ServerMsg::ProgramModes {
+                        program_id: self.program_id.clone(),
+                        modes: self.dec_modes.enabled().clone(),
+                    };
+                    self.server_tx.send(event).unwrap();
+                }
+
+                for string in self.osc_scanner.scan(&bytes) {
+                    self.handle_osc(&string);
+                }
+            }
+            VteWorkerMsg::PtyReadZero => info!("got PtyReadZero"),
+            VteWorkerMsg::PtyReadError => info!("got PtyReadError"),
+            VteWorkerMsg::RequestRedrawRect{rect} => self.redraw_rect(rect),
+            VteWorkerMsg::Resize{size} => self.resize(size),
+            VteWorkerMsg::FetchScrollback{client_id, start, count} => {
+                let event = ::server::ServerMsg::ProgramScrollback {
+                    client_id: client_id,
+                    program_id: self.program_id.clone(),
+                    start: start,
+                    lines: self.scrollback.lines(start, count),
+                    total: self.scrollback.len(),
+                };
+                self.server_tx.send(event).unwrap();
+            }
+            VteWorkerMsg::ChildExited{status} => {
+                let event = ::server::ServerMsg::ProgramExited {
+                    program_id: self.program_id.clone(),
+                    status: status,
+                };
+                self.server_tx.send(event).unwrap();
             }
-            VteWorkerMsg::PtyReadZero => error!("got PtyReadZero"),
-            VteWorkerMsg::PtyReadError => error!("got PtyReadError"),
-            VteWorkerMsg::RequestRedrawRect{rect: _} => info!("got RequestRedrawRect msg"),
+            VteWorkerMsg::Quit => {}
         }
     }
+
+    /// vterm reflows its screen to the new size. Subscribers get the whole thing, since their
+    /// copy of it is now the wrong shape.
+    fn resize(&mut self, size: Size) {
+        trace!("resize {:?}", size);
+        self.vterm.set_size(&size);
+        self.vterm.screen_flush_damage();
+        self.redraw_rect(Rect::new(Pos::new(0, 0), size));
+    }
+
+    /// Send the cells in the rect whether or not they've changed, followed by the cursor since it
+    /// was probably drawn over.
+    fn redraw_rect(&mut self, rect: Rect) {
+        trace!("redraw_rect {:?}", rect);
+
+        let screen_rect = Rect::new(Pos::new(0, 0), self.vterm.get_size());
+        let rect = match rect.intersection(&screen_rect) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        let event = ::server::ServerMsg::ProgramDamage {
+            program_id: self.program_id.clone(),
+            cells: self.vterm.screen_get_cells_in_rect(&rect),
+            rect: rect,
+        };
+        self.server_tx.send(event).unwrap();
+
+        let event = ::server::ServerMsg::ProgramMoveCursor {
+            program_id: self.program_id.clone(),
+            new: self.cursor_pos.clone(),
+            old: self.cursor_pos.clone(),
+            is_visible: self.is_cursor_visible,
+        };
+        self.server_tx.send(event).unwrap();
+    }
 }
@@ -0,0 +1,140 @@
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use super::*;
+use protocol;
+use transport::{self, Tx};
+use uuid::Uuid;
+
+/// Accepts client connections on a unix socket. Each connection becomes a Client of the server,
+/// and is removed again when the connection closes.
+pub struct SocketListener {
+    listener: UnixListener,
+    /// the server's id, sent in the hello
+    server_id: String,
+    server_tx: Sender<ServerMsg>,
+}
+
+impl SocketListener {
+    /// Bind to the socket at path. Fails if another server is already listening there.
+    pub fn spawn(path: &Path,
+                 server_id: &str,
+                 server_tx: Sender<ServerMsg>)
+                 -> io::Result<JoinHandle<()>> {
+        let listener = try!(SocketListener::bind(path));
+
+        info!("spawning socket listener on {:?}", path);
+        let server_id = server_id.to_string();
+        let handle = thread::spawn(move || {
+            let mut worker = SocketListener {
+                listener: listener,
+                server_id: server_id,
+                server_tx: server_tx,
+            };
+            worker.enter_accept_loop();
+            info!("exiting socket listener");
+        });
+
+        Ok(handle)
+    }
+
+    fn bind(path: &Path) -> io::Result<UnixListener> {
+        if let Some(dir) = path.parent() {
+            try!(DirBuilder::new().recursive(true).mode(0o700).create(dir));
+        }
+
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse,
+                                          format!("a server is already listening on {:?}",
+                                                  path)));
+            }
+
+            // left behind by a server that didn't exit cleanly
+            info!("removing stale socket {:?}", path);
+            try!(fs::remove_file(path));
+        }
+
+        UnixListener::bind(path)
+    }
+
+    fn enter_accept_loop(&mut self) {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("accepting connection failed: {}", e);
+                    continue;
+                }
+            };
+
+            // the handshake gets its own thread, so a peer that never says hello doesn't hold up
+            // everyone else
+            let server_id = self.server_id.clone();
+            let server_tx = self.server_tx.clone();
+            thread::spawn(move || add_client(stream, &server_id, server_tx));
+        }
+    }
+}
+
+/// Say hello to a newly connected peer, then make it a Client of the server until the connection
+/// closes. Peers that don't say hello within 5 seconds are dropped.
+///
+/// The client is given an id of its own rather than going by the one in its hello, and every msg
+/// read from it is stamped with that id.
+fn add_client(mut stream: UnixStream, server_id: &str, server_tx: Sender<ServerMsg>) {
+    stream.set_read_timeout(Some(Duration::from_secs(5))).ok();
+    let hello = match protocol::handshake(&mut stream, server_id) {
+        Ok(hello) => hello,
+        Err(e) => {
+            warn!("dropping client: {}", e);
+            return;
+        }
+    };
+    stream.set_read_timeout(None).ok();
+
+    let client_id = Uuid::new_v4().to_hyphenated_string();
+    info!("client {:?} connected as {:?}", hello.id, client_id);
+
+    let reader = match stream.try_clone() {
+        Ok(reader) => reader,
+        Err(e) => {
+            error!("couldnt clone client stream: {}", e);
+            return;
+        }
+    };
+
+    let msg = ServerMsg::ClientAdd { client: Client::new(&client_id, Tx::socket(stream)) };
+    if server_tx.send(msg).is_err() {
+        warn!("server is gone, dropping client");
+        return;
+    }
+
+    let on_close = ServerMsg::ClientRemote { client_id: client_id.clone() };
+    transport::spawn_socket_reader_with(reader,
+                                        server_tx,
+                                        Some(on_close),
+                                        move |msg: ServerMsg| msg.from_client(&client_id));
+}
+
+/// Removes the socket file when dropped, so a server that exits normally doesn't leave it around.
+pub struct SocketFile {
+    path: PathBuf,
+}
+
+impl SocketFile {
+    pub fn new(path: &Path) -> SocketFile {
+        SocketFile { path: path.to_path_buf() }
+    }
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
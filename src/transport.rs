@@ -0,0 +1,111 @@
+use libc;
+use protocol::{self, Wire};
+use std::env;
+use std::fmt;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// A handle for sending msgs to the other side of a client server connection. The other side can
+/// be a thread in this process, or another process at the end of a unix socket.
+pub enum Tx<T> {
+    Channel(Sender<T>),
+    Socket(Arc<Mutex<UnixStream>>),
+}
+
+impl<T: Wire> Tx<T> {
+    pub fn socket(stream: UnixStream) -> Tx<T> {
+        Tx::Socket(Arc::new(Mutex::new(stream)))
+    }
+
+    pub fn send(&self, msg: T) -> io::Result<()> {
+        match *self {
+            Tx::Channel(ref tx) => {
+                tx.send(msg)
+                  .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "receiver hung up"))
+            }
+            Tx::Socket(ref stream) => {
+                let mut stream = stream.lock().unwrap();
+                protocol::write_msg(&mut *stream, &msg)
+            }
+        }
+    }
+}
+
+impl<T> Clone for Tx<T> {
+    fn clone(&self) -> Tx<T> {
+        match *self {
+            Tx::Channel(ref tx) => Tx::Channel(tx.clone()),
+            Tx::Socket(ref stream) => Tx::Socket(stream.clone()),
+        }
+    }
+}
+
+impl<T> From<Sender<T>> for Tx<T> {
+    fn from(tx: Sender<T>) -> Tx<T> {
+        Tx::Channel(tx)
+    }
+}
+
+impl<T> fmt::Debug for Tx<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Tx::Channel(_) => write!(fmt, "Tx::Channel"),
+            Tx::Socket(_) => write!(fmt, "Tx::Socket"),
+        }
+    }
+}
+
+/// Spawn a thread that reads msgs off the socket and passes them to the given Sender. When the
+/// socket closes or garbage is read, the `on_close` msg is sent and the thread exits.
+pub fn spawn_socket_reader<T>(stream: UnixStream,
+                              tx: Sender<T>,
+                              on_close: Option<T>)
+                              -> JoinHandle<()>
+    where T: 'static + Wire + Send
+{
+    spawn_socket_reader_with(stream, tx, on_close, |msg| msg)
+}
+
+/// Like spawn_socket_reader, but each msg goes through `map` on its way to the Sender
+pub fn spawn_socket_reader_with<T, F>(mut stream: UnixStream,
+                                      tx: Sender<T>,
+                                      on_close: Option<T>,
+                                      map: F)
+                                      -> JoinHandle<()>
+    where T: 'static + Wire + Send,
+          F: 'static + Fn(T) -> T + Send
+{
+    info!("spawning socket reader");
+    thread::spawn(move || {
+        loop {
+            let msg = match protocol::read_msg(&mut stream) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    info!("socket reader stopped: {}", e);
+                    break;
+                }
+            };
+
+            if tx.send(map(msg)).is_err() {
+                break;
+            }
+        }
+
+        if let Some(msg) = on_close {
+            tx.send(msg).ok();
+        }
+        info!("exiting socket reader");
+    })
+}
+
+/// Where a server listens unless told otherwise. Like tmux, it lives in a directory that only the
+/// user can access: `$TMPDIR/intermix-$UID/default`.
+pub fn default_socket_path() -> PathBuf {
+    let dir = env::var_os("TMPDIR").map(PathBuf::from).unwrap_or(PathBuf::from("/tmp"));
+    let uid = unsafe { libc::getuid() };
+    dir.join(format!("intermix-{}", uid)).join("default")
+}
@@ -68,6 +68,7 @@ fn load_vterm_events_into_client(vterm: &mut VTerm, client: &mut Client) {
                     ScreenEvent::Damage(e) => {
                         info!("Damage: {:?}", e);
                         let event = ClientMsg::ProgramDamage {
+                            server_id: "test_server".to_string(),
                             program_id: "test_program".to_string(),
                             cells: vterm.screen_get_cells_in_rect(&e.rect),
                             rect: e.rect,
@@ -77,6 +78,7 @@ fn load_vterm_events_into_client(vterm: &mut VTerm, client: &mut Client) {
                     ScreenEvent::MoveCursor(e) => {
                         info!("MoveCursor: {:?}", e);
                         let event = ClientMsg::ProgramMoveCursor {
+                            server_id: "test_server".to_string(),
                             program_id: "test_program".to_string(),
                             new: e.new,
                             old: e.old,
@@ -87,12 +89,14 @@ fn load_vterm_events_into_client(vterm: &mut VTerm, client: &mut Client) {
                     ScreenEvent::MoveRect(e) => {
                         info!("MoveRect: {:?}", e);
                         let event = ClientMsg::ProgramDamage {
+                            server_id: "test_server".to_string(),
                             program_id: "test_program".to_string(),
                             cells: vterm.screen_get_cells_in_rect(&e.src),
                             rect: e.src,
                         };
                         client.tx().send(event).unwrap();
                         let event = ClientMsg::ProgramDamage {
+                            server_id: "test_server".to_string(),
                             program_id: "test_program".to_string(),
                             cells: vterm.screen_get_cells_in_rect(&e.dest),
                             rect: e.dest,
@@ -126,7 +130,8 @@ fn build_client(output: TestIO, size: &Size) -> Client {
                                                 rows: size.height,
                                                 cols: size.width,
                                                 ..Default::default()
-                                            });
+                                            },
+                                            Default::default());
 
     let mut layout = layout::Layout::new(Size {
         height: size.height,
@@ -134,7 +139,7 @@ fn build_client(output: TestIO, size: &Size) -> Client {
     });
 
     let leaf = layout::WrapBuilder::row()
-                   .name("test_program".to_string())
+                   .name(servers::wrap_name_for("test_server", "test_program"))
                    .width(80)
                    .height(24)
                    .build();
@@ -191,6 +196,73 @@ fn it_draws_simple_echo_output() {
     }
 }
 
+#[test]
+fn it_draws_wide_and_combining_chars() {
+    ::setup_logging();
+
+    let size = Size { height: 2, width: 8 };
+
+    let mut expected_vterm: VTerm = run_command_in_vterm(CommandBuilder::new("printf")
+                                                             .arg("\u{4e2d}\u{6587}e\u{301}x"),
+                                                         size.clone());
+    let mut test_output = TestIO::new();
+    let mut client = build_client(test_output.clone(), &size);
+    load_vterm_events_into_client(&mut expected_vterm, &mut client);
+
+    let mut actual_vterm = build_vterm(&size);
+
+    let result = ::try_until_ok(move || {
+        let mut bytes: Vec<u8> = vec![];
+        test_output.read_to_end(&mut bytes).unwrap();
+        actual_vterm.write(&bytes).unwrap();
+        let diff = VTermDiff::new(&expected_vterm, &actual_vterm);
+        if diff.has_diff() {
+            Err(format!("{}", diff))
+        } else {
+            Ok(())
+        }
+    });
+
+    match result {
+        Ok(()) => {}
+        Err(diff) => assert!(false, diff),
+    }
+}
+
+#[test]
+fn it_draws_strikes_and_underline_styles() {
+    ::setup_logging();
+
+    let size = Size { height: 2, width: 8 };
+
+    let mut expected_vterm: VTerm =
+        run_command_in_vterm(CommandBuilder::new("printf")
+                                 .arg("\x1b[9ms\x1b[29m\x1b[4mu\x1b[21md\x1b[24m\x1b[11mf\x1b[m"),
+                             size.clone());
+    let mut test_output = TestIO::new();
+    let mut client = build_client(test_output.clone(), &size);
+    load_vterm_events_into_client(&mut expected_vterm, &mut client);
+
+    let mut actual_vterm = build_vterm(&size);
+
+    let result = ::try_until_ok(move || {
+        let mut bytes: Vec<u8> = vec![];
+        test_output.read_to_end(&mut bytes).unwrap();
+        actual_vterm.write(&bytes).unwrap();
+        let diff = VTermDiff::new(&expected_vterm, &actual_vterm);
+        if diff.has_diff() {
+            Err(format!("{}", diff))
+        } else {
+            Ok(())
+        }
+    });
+
+    match result {
+        Ok(()) => {}
+        Err(diff) => assert!(false, diff),
+    }
+}
+
 #[test]
 fn it_draws_simple_vim_session() {
     ::setup_logging();
@@ -58,7 +58,8 @@ fn client_starts_in_welcome_mode() {
                                     TtyIoCtlConfig {
                                         rows: 5,
                                         cols: 10,
-                                    });
+                                    },
+                                    Default::default());
     let mut vterm = build_vterm(&Size {
         height: 5,
         width: 10,
@@ -78,7 +79,8 @@ fn client_can_enter_command_mode() {
                                      TtyIoCtlConfig {
                                          rows: 5,
                                          cols: 10,
-                                     });
+                                     },
+                                     Default::default());
 
     let mut vterm = build_vterm(&Size {
         height: 5,
@@ -100,7 +102,8 @@ fn client_can_enter_program_mode() {
                                      TtyIoCtlConfig {
                                          rows: 24,
                                          cols: 80,
-                                     });
+                                     },
+                                     Default::default());
 
     let mut vterm = build_vterm(&Size {
         height: 24,
@@ -115,6 +118,7 @@ fn client_can_enter_program_mode() {
     tx.send(ClientMsg::ProgramAdd {
           server_id: "some server".to_string(),
           program_id: "123".to_string(),
+          size: Size::new(78, 21),
       })
       .unwrap();
 
@@ -133,7 +137,8 @@ fn client_can_exit_program_mode() {
                                      TtyIoCtlConfig {
                                          rows: 24,
                                          cols: 80,
-                                     });
+                                     },
+                                     Default::default());
 
     // The screen size here is hard coded through the client code. Need to fix that.
     let mut vterm = build_vterm(&Size {
@@ -149,6 +154,7 @@ fn client_can_exit_program_mode() {
     tx.send(ClientMsg::ProgramAdd {
           server_id: "some server".to_string(),
           program_id: "123".to_string(),
+          size: Size::new(78, 21),
       })
       .unwrap();
     status_line_matches(&mut vterm, &mut output, Regex::new(r"program").unwrap());
@@ -158,3 +164,32 @@ fn client_can_exit_program_mode() {
 
     client.stop();
 }
+
+#[test]
+fn client_repaints_after_tty_resize() {
+    ::setup_logging();
+    let mut output = TestIO::new();
+    let input = TestIO::new();
+
+    let (tx, client) = Client::spawn(input.clone(),
+                                     output.clone(),
+                                     TtyIoCtlConfig {
+                                         rows: 5,
+                                         cols: 10,
+                                     },
+                                     Default::default());
+    let mut vterm = build_vterm(&Size {
+        height: 5,
+        width: 10,
+    });
+    status_line_matches(&mut vterm, &mut output, Regex::new(r"welcome").unwrap());
+
+    tx.send(ClientMsg::TtyResize { size: Size::new(20, 8) }).unwrap();
+
+    let mut vterm = build_vterm(&Size {
+        height: 8,
+        width: 20,
+    });
+    status_line_matches(&mut vterm, &mut output, Regex::new(r"welcome").unwrap());
+    client.stop();
+}
@@ -301,6 +301,30 @@ fn it_draws_a_bunch_of_columns() {
 ········");
 }
 
+#[test]
+fn it_lays_out_again_after_removing_a_wrap() {
+    let rows = vec![
+        WrapBuilder::row().name("a".to_string()).height(1).build(),
+        WrapBuilder::row().name("b".to_string()).height(1).build(),
+        WrapBuilder::row().name("c".to_string()).height(1).build(),
+    ];
+
+    let mut layout = Layout::new(Size { height: 2, width: 1 });
+    for row in rows {
+        layout.tree_mut().root_mut().append(row);
+    }
+    layout.remove_wrap("b");
+    layout.flush_changes();
+
+    assert_eq!(layout.tree().values().count(), 3);
+    assert_scene_eq(&draw_layout(&layout),
+                    "
+···
+·a·
+·c·
+···");
+}
+
 #[test]
 fn it_draws_a_bunch_of_rows() {
     let rows = vec![
@@ -1,7 +1,8 @@
 use std::io::prelude::*;
-use libintermix::client::paint::Pen;
+use libintermix::client::paint::{ColorSupport, Pen};
 use term::terminfo::TermInfo;
 use term::terminfo::parm::Variables;
+use vterm_sys::ColorRGB;
 
 fn find_subsequence_slowly(haystack: &[u8], needle: &[u8]) -> Option<usize> {
     haystack.windows(needle.len()).position(|window| window == needle)
@@ -20,7 +21,11 @@ fn stringify_byte_array_as_hex(bytes: &[u8]) -> String {
 }
 
 fn flush_pen(pen: &mut Pen) -> Vec<u8> {
-    let terminfo = TermInfo::from_name("xterm").unwrap();
+    flush_pen_for(pen, "xterm")
+}
+
+fn flush_pen_for(pen: &mut Pen, term: &str) -> Vec<u8> {
+    let terminfo = TermInfo::from_name(term).unwrap();
     let mut vars = Variables::new();
     pen.flush(&terminfo, &mut vars)
 }
@@ -85,3 +90,73 @@ fn pen_can_turn_off_invisible() {
     assert_has_bytes(&flush_pen(&mut pen), b"\x1b[?12l\x1b[?25h");
     assert_eq!(flush_pen(&mut pen).len(), 0);
 }
+
+#[test]
+fn pen_can_turn_strike_on_and_off() {
+    let mut pen = Pen::new();
+    flush_pen(&mut pen);
+
+    pen.strike = true;
+    assert_has_bytes(&flush_pen(&mut pen), b"\x1b[9m");
+    pen.strike = false;
+    assert_has_bytes(&flush_pen(&mut pen), b"\x1b[29m");
+    assert_eq!(flush_pen(&mut pen).len(), 0);
+}
+
+#[test]
+fn pen_can_double_underline() {
+    let mut pen = Pen::new();
+    flush_pen(&mut pen);
+
+    pen.underline = 2;
+    assert_has_bytes(&flush_pen(&mut pen), b"\x1b[21m");
+    assert_eq!(flush_pen(&mut pen).len(), 0);
+}
+
+#[test]
+fn pen_single_underlines_curly_underline_without_smulx() {
+    let mut pen = Pen::new();
+    flush_pen(&mut pen);
+
+    pen.underline = 3;
+    assert_has_bytes(&flush_pen(&mut pen), b"\x1b[4m");
+}
+
+#[test]
+fn pen_can_change_fonts() {
+    let mut pen = Pen::new();
+    flush_pen(&mut pen);
+
+    pen.font = 1;
+    assert_has_bytes(&flush_pen(&mut pen), b"\x1b[11m");
+    assert_eq!(flush_pen(&mut pen).len(), 0);
+}
+
+#[test]
+fn pen_sends_direct_colors_to_true_color_terminals() {
+    let mut pen = Pen::new();
+    pen.color_support = ColorSupport::TrueColor;
+    flush_pen(&mut pen);
+
+    pen.fg_rgb = Some(ColorRGB {
+        red: 1,
+        green: 2,
+        blue: 3,
+    });
+    assert_has_bytes(&flush_pen(&mut pen), b"\x1b[38;2;1;2;3m");
+    assert_eq!(flush_pen(&mut pen).len(), 0);
+}
+
+#[test]
+fn pen_sends_the_nearest_palette_color_to_other_terminals() {
+    let mut pen = Pen::new();
+    pen.color_support = ColorSupport::Colors256;
+    flush_pen_for(&mut pen, "xterm-256color");
+
+    pen.bg_rgb = Some(ColorRGB {
+        red: 250,
+        green: 5,
+        blue: 5,
+    });
+    assert_has_bytes(&flush_pen_for(&mut pen, "xterm-256color"), b"\x1b[48;5;196m");
+}
@@ -11,6 +11,7 @@ extern crate vterm_sys;
 mod client;
 mod server;
 mod support;
+mod transport;
 
 static mut is_logging_setup: bool = false;
 
@@ -1,4 +1,671 @@
+use libintermix::client::ClientMsg;
+use libintermix::server::{Client, Server, ServerConfig, ServerMsg};
+use libintermix::transport::Tx;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use vterm_sys::{Pos, Size};
+
 #[test]
 fn it_works() {
     assert!(true);
 }
+
+fn add_client(server_tx: &Sender<ServerMsg>, client_id: &str) -> Receiver<ClientMsg> {
+    let (tx, rx) = channel::<ClientMsg>();
+    server_tx.send(ServerMsg::ClientAdd { client: Client::new(client_id, Tx::Channel(tx)) })
+             .unwrap();
+    rx
+}
+
+fn start_program(server_tx: &Sender<ServerMsg>, program_id: &str) {
+    server_tx.send(ServerMsg::ProgramStart {
+                 program_id: program_id.to_string(),
+                 command_and_args: vec!["cat".to_string()],
+                 command_line: None,
+                 cwd: None,
+                 env: vec![],
+                 size: Size::new(80, 24),
+                 monitor_silence: false,
+             })
+             .unwrap();
+}
+
+fn start_command(server_tx: &Sender<ServerMsg>, program_id: &str, command_line: &str) {
+    server_tx.send(ServerMsg::ProgramStart {
+                 program_id: program_id.to_string(),
+                 command_and_args: vec![],
+                 command_line: Some(command_line.to_string()),
+                 cwd: None,
+                 env: vec![],
+                 size: Size::new(80, 24),
+                 monitor_silence: false,
+             })
+             .unwrap();
+}
+
+// Returns true if the client is eventually told about the program
+fn is_program_added(rx: &Receiver<ClientMsg>, program_id: &str) -> bool {
+    ::is_ultimately_true(|| {
+        while let Ok(msg) = rx.try_recv() {
+            if let ClientMsg::ProgramAdd { program_id: ref id, .. } = msg {
+                if id == program_id {
+                    return true;
+                }
+            }
+        }
+        false
+    })
+}
+
+// Returns true if the client is eventually sent damage for the program
+fn is_program_damaged(rx: &Receiver<ClientMsg>, program_id: &str) -> bool {
+    ::is_ultimately_true(|| {
+        while let Ok(msg) = rx.try_recv() {
+            if let ClientMsg::ProgramDamage { program_id: ref id, .. } = msg {
+                if id == program_id {
+                    return true;
+                }
+            }
+        }
+        false
+    })
+}
+
+#[test]
+fn programs_are_announced_to_every_client() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    let b = add_client(&server_tx, "b");
+
+    start_program(&server_tx, "123");
+
+    assert!(is_program_added(&a, "123"));
+    assert!(is_program_added(&b, "123"));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn clients_that_attach_later_are_told_about_running_programs() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    start_program(&server_tx, "123");
+
+    let a = add_client(&server_tx, "a");
+
+    assert!(is_program_added(&a, "123"));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn server_keeps_going_when_a_client_goes_away() {
+    ::setup_logging();
+    let (server_tx, handle) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    let b = add_client(&server_tx, "b");
+
+    drop(a);
+    start_program(&server_tx, "123");
+    start_program(&server_tx, "456");
+
+    assert!(is_program_added(&b, "123"));
+    assert!(is_program_added(&b, "456"));
+
+    server_tx.send(ServerMsg::Quit).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn only_subscribed_clients_are_sent_damage() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    let b = add_client(&server_tx, "b");
+    start_program(&server_tx, "123");
+    assert!(is_program_added(&b, "123"));
+
+    server_tx.send(ServerMsg::ProgramSubscribe {
+                 client_id: "a".to_string(),
+                 program_id: "123".to_string(),
+             })
+             .unwrap();
+
+    assert!(is_program_damaged(&a, "123"));
+    // damage goes to every subscriber in one go, so b would have it by now
+    assert!(b.try_recv().is_err());
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn damage_says_which_server_it_came_from() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("build box", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_program(&server_tx, "123");
+    server_tx.send(ServerMsg::ProgramSubscribe {
+                 client_id: "a".to_string(),
+                 program_id: "123".to_string(),
+             })
+             .unwrap();
+
+    let is_from_build_box = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramDamage { ref server_id, .. } = msg {
+                return server_id == "build box";
+            }
+        }
+        false
+    });
+    assert!(is_from_build_box);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn resizing_a_program_tells_every_client() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_program(&server_tx, "123");
+    assert!(is_program_added(&a, "123"));
+
+    server_tx.send(ServerMsg::ProgramResize {
+                 program_id: "123".to_string(),
+                 size: Size::new(100, 40),
+             })
+             .unwrap();
+
+    let is_resized = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramResize { ref program_id, ref size, .. } = msg {
+                return program_id == "123" && *size == Size::new(100, 40);
+            }
+        }
+        false
+    });
+    assert!(is_resized);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn exited_programs_are_reported_with_their_status() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "exit 3");
+
+    assert!(is_program_exited(&a, "123", 3));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn programs_start_in_the_given_directory_with_the_given_env() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    server_tx.send(ServerMsg::ProgramStart {
+                 program_id: "123".to_string(),
+                 command_and_args: vec![],
+                 command_line: Some("test \"$(pwd)\" = / && test \"$FOO\" = bar".to_string()),
+                 cwd: Some("/".to_string()),
+                 env: vec!["FOO=bar".to_string()],
+                 size: Size::new(80, 24),
+                 monitor_silence: false,
+             })
+             .unwrap();
+
+    assert!(is_program_exited(&a, "123", 0));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn commands_are_found_on_the_path_and_bare_env_names_are_unset() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    server_tx.send(ServerMsg::ProgramStart {
+                 program_id: "123".to_string(),
+                 command_and_args: vec!["sh".to_string(),
+                                        "-c".to_string(),
+                                        "test -z \"${HOME+set}\"".to_string()],
+                 command_line: None,
+                 cwd: None,
+                 env: vec!["HOME".to_string()],
+                 size: Size::new(80, 24),
+                 monitor_silence: false,
+             })
+             .unwrap();
+
+    assert!(is_program_exited(&a, "123", 0));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn programs_are_told_they_are_in_intermix() {
+    ::setup_logging();
+    let config = ServerConfig { term: "some-term".to_string(), ..Default::default() };
+    let (server_tx, _) = Server::spawn("some server", config);
+    let a = add_client(&server_tx, "a");
+    let script = "test \"$TERM\" = some-term && test \"$INTERMIX\" = 'some server' && \
+                  test \"$INTERMIX_PROGRAM_ID\" = 123 && test \"$COLORTERM\" = truecolor";
+    start_command(&server_tx, "123", script);
+
+    assert!(is_program_exited(&a, "123", 0));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn lines_that_scroll_off_the_screen_can_be_fetched() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    server_tx.send(ServerMsg::ProgramStart {
+                 program_id: "123".to_string(),
+                 command_and_args: vec!["seq".to_string(), "30".to_string()],
+                 command_line: None,
+                 cwd: None,
+                 env: vec![],
+                 size: Size::new(80, 24),
+                 monitor_silence: false,
+             })
+             .unwrap();
+    assert!(is_program_exited(&a, "123", 0));
+
+    server_tx.send(ServerMsg::ProgramScrollbackFetch {
+                 client_id: "a".to_string(),
+                 program_id: "123".to_string(),
+                 start: 0,
+                 count: 100,
+             })
+             .unwrap();
+
+    let has_first_line = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramScrollback { lines, total, .. } = msg {
+                return total == lines.len() && lines[0][0].chars == b"1".to_vec();
+            }
+        }
+        false
+    });
+    assert!(has_first_line);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn buffers_can_be_pasted_into_programs() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "read line && test \"$line\" = hello");
+
+    server_tx.send(ServerMsg::BufferSet {
+                 name: "default".to_string(),
+                 bytes: b"hello\n".to_vec(),
+             })
+             .unwrap();
+    server_tx.send(ServerMsg::BufferPaste {
+                 name: "default".to_string(),
+                 program_id: "123".to_string(),
+             })
+             .unwrap();
+
+    assert!(is_program_exited(&a, "123", 0));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn pastes_are_bracketed_for_programs_that_ask() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    // the end marker of one bracketed paste comes before the start of the next, on one line
+    let command_line = "printf '\\033[?2004h'; \
+                        start=\"$(printf '\\033[200~hi')\"; end=\"$(printf '\\033[201~')\"; \
+                        while read -r line && test \"$line\" != \"$start\"; do :; done; \
+                        read -r line && test \"$line\" = \"$end$start\"";
+    start_command(&server_tx, "123", command_line);
+    server_tx.send(ServerMsg::BufferSet {
+                 name: "default".to_string(),
+                 bytes: b"hi\n".to_vec(),
+             })
+             .unwrap();
+
+    // pastes from before the program turned on bracketed paste aren't bracketed, so keep pasting
+    let is_exited = ::is_ultimately_true(|| {
+        server_tx.send(ServerMsg::BufferPaste {
+                     name: "default".to_string(),
+                     program_id: "123".to_string(),
+                 })
+                 .unwrap();
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramExited { status, .. } = msg {
+                return status == 0;
+            }
+        }
+        false
+    });
+    assert!(is_exited);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn programs_that_dont_read_their_input_dont_hold_up_the_server() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "sleep 30");
+    // far more than a pty buffers
+    server_tx.send(ServerMsg::BufferSet {
+                 name: "default".to_string(),
+                 bytes: vec![b'x'; 1024 * 1024],
+             })
+             .unwrap();
+    server_tx.send(ServerMsg::BufferPaste {
+                 name: "default".to_string(),
+                 program_id: "123".to_string(),
+             })
+             .unwrap();
+
+    start_command(&server_tx, "456", "exit 3");
+    assert!(is_program_exited(&a, "456", 3));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn programs_setting_the_clipboard_are_passed_on_to_clients() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "printf '\\033]52;c;aGVsbG8=\\007'");
+
+    let has_clipboard = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::Clipboard { bytes, .. } = msg {
+                return bytes == b"hello".to_vec();
+            }
+        }
+        false
+    });
+    assert!(has_clipboard);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn programs_setting_the_clipboard_dont_change_what_is_pasted() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    server_tx.send(ServerMsg::BufferSet {
+                 name: "default".to_string(),
+                 bytes: b"ls\n".to_vec(),
+             })
+             .unwrap();
+    start_command(&server_tx, "123", "printf '\\033]52;c;ZXZpbAo=\\007'");
+    assert!(is_program_exited(&a, "123", 0));
+
+    start_command(&server_tx, "456", "read line && test \"$line\" = ls");
+    server_tx.send(ServerMsg::BufferPaste {
+                 name: "default".to_string(),
+                 program_id: "456".to_string(),
+             })
+             .unwrap();
+
+    assert!(is_program_exited(&a, "456", 0));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn program_titles_are_passed_on_to_clients() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "printf '\\033]1;vi\\007\\033]2;vim notes\\007'");
+
+    let has_title = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramTitle { title, icon_name, .. } = msg {
+                if title == "vim notes" {
+                    return icon_name == "vi";
+                }
+            }
+        }
+        false
+    });
+    assert!(has_title);
+
+    // clients that attach later are told too
+    let b = add_client(&server_tx, "b");
+    let has_title = ::is_ultimately_true(|| {
+        while let Ok(msg) = b.try_recv() {
+            if let ClientMsg::ProgramTitle { title, .. } = msg {
+                return title == "vim notes";
+            }
+        }
+        false
+    });
+    assert!(has_title);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn cursor_styles_are_passed_on_to_clients() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "printf '\\033[6 q'");
+
+    let has_style = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramCursorStyle { style, .. } = msg {
+                return style == 6;
+            }
+        }
+        false
+    });
+    assert!(has_style);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn programs_hiding_the_cursor_are_passed_on_to_subscribers() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "printf '\\033[?25l'; sleep 5");
+    server_tx.send(ServerMsg::ProgramSubscribe {
+                 client_id: "a".to_string(),
+                 program_id: "123".to_string(),
+             })
+             .unwrap();
+
+    let is_hidden = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramMoveCursor { is_visible: false, .. } = msg {
+                return true;
+            }
+        }
+        false
+    });
+    assert!(is_hidden);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn bells_are_passed_on_to_clients() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_command(&server_tx, "123", "printf '\\007'");
+
+    let has_bell = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramBell { program_id, .. } = msg {
+                return program_id == "123";
+            }
+        }
+        false
+    });
+    assert!(has_bell);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn clients_are_told_when_programs_become_active_and_go_silent() {
+    ::setup_logging();
+    let config = ServerConfig {
+        silence_period: Duration::from_millis(500),
+        ..Default::default()
+    };
+    let (server_tx, _) = Server::spawn("some server", config);
+    let a = add_client(&server_tx, "a");
+    // both go quiet, but only the monitored one is reported
+    for program_id in &["123", "456"] {
+        start_command(&server_tx, program_id, "echo building; sleep 5");
+    }
+    server_tx.send(ServerMsg::ProgramMonitorSilence {
+                 program_id: "456".to_string(),
+                 is_on: true,
+             })
+             .unwrap();
+
+    let mut is_active = false;
+    let is_silent = ::is_ultimately_true(|| {
+        while let Ok(msg) = a.try_recv() {
+            match msg {
+                ClientMsg::ProgramActivity { ref program_id, .. } if program_id == "456" => {
+                    is_active = true
+                }
+                ClientMsg::ProgramSilence { program_id, .. } => {
+                    return is_active && program_id == "456"
+                }
+                _ => {}
+            }
+        }
+        false
+    });
+    assert!(is_silent);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+// Returns true if the client is eventually told the program is monitored for silence
+fn is_program_monitored_for_silence(rx: &Receiver<ClientMsg>, program_id: &str) -> bool {
+    ::is_ultimately_true(|| {
+        while let Ok(msg) = rx.try_recv() {
+            if let ClientMsg::ProgramMonitorSilence { program_id: ref id, is_on, .. } = msg {
+                if id == program_id {
+                    return is_on;
+                }
+            }
+        }
+        false
+    })
+}
+
+#[test]
+fn every_client_is_told_when_a_program_is_monitored_for_silence() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    let b = add_client(&server_tx, "b");
+    start_program(&server_tx, "123");
+    assert!(is_program_added(&a, "123"));
+
+    // as if a's user turned it on, so b's user turns it off rather than on again
+    server_tx.send(ServerMsg::ProgramMonitorSilence {
+                 program_id: "123".to_string(),
+                 is_on: true,
+             })
+             .unwrap();
+    assert!(is_program_monitored_for_silence(&b, "123"));
+
+    // and a client that attaches later doesn't show it as off
+    let c = add_client(&server_tx, "c");
+    assert!(is_program_monitored_for_silence(&c, "123"));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn mouse_events_are_passed_on_to_programs_that_ask_for_them() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    let command_line = "stty raw -echo; printf '\\033[?1000h\\033[?1006h'; \
+                        test \"$(head -c 9)\" = \"$(printf '\\033[<0;3;2M')\"";
+    start_command(&server_tx, "123", command_line);
+
+    // events from before the program turned on mouse reporting are dropped, so keep clicking
+    let is_exited = ::is_ultimately_true(|| {
+        server_tx.send(ServerMsg::ProgramMouse {
+                     program_id: "123".to_string(),
+                     button: 0,
+                     pos: Pos::new(2, 1),
+                     is_release: false,
+                 })
+                 .unwrap();
+        while let Ok(msg) = a.try_recv() {
+            if let ClientMsg::ProgramExited { status, .. } = msg {
+                return status == 0;
+            }
+        }
+        false
+    });
+    assert!(is_exited);
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+// Returns true if the client is eventually told the program exited with this status
+fn is_program_exited(rx: &Receiver<ClientMsg>, program_id: &str, status: u8) -> bool {
+    ::is_ultimately_true(|| {
+        while let Ok(msg) = rx.try_recv() {
+            if let ClientMsg::ProgramExited { program_id: ref id, status: s, .. } = msg {
+                return id == program_id && s == status;
+            }
+        }
+        false
+    })
+}
+
+#[test]
+fn killed_programs_exit_with_the_signal() {
+    ::setup_logging();
+    let (server_tx, _) = Server::spawn("some server", Default::default());
+    let a = add_client(&server_tx, "a");
+    start_program(&server_tx, "123");
+    assert!(is_program_added(&a, "123"));
+
+    server_tx.send(ServerMsg::ProgramKill {
+                 program_id: "123".to_string(),
+                 signal: 15,
+             })
+             .unwrap();
+
+    // SIGTERM is 15
+    assert!(is_program_exited(&a, "123", 128 + 15));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
+
+#[test]
+fn programs_ignoring_sigterm_are_killed_after_the_grace_period() {
+    ::setup_logging();
+    let config = ServerConfig {
+        kill_grace_period: Duration::from_millis(500),
+        ..Default::default()
+    };
+    let (server_tx, _) = Server::spawn("some server", config);
+    let a = add_client(&server_tx, "a");
+    let script = "trap '' TERM; while true; do sleep 1; done";
+    start_command(&server_tx, "123", script);
+    assert!(is_program_added(&a, "123"));
+    // give sh time to set its trap
+    thread::sleep(Duration::from_millis(500));
+
+    server_tx.send(ServerMsg::ProgramKill {
+                 program_id: "123".to_string(),
+                 signal: 15,
+             })
+             .unwrap();
+
+    // SIGKILL is 9
+    assert!(is_program_exited(&a, "123", 128 + 9));
+    server_tx.send(ServerMsg::Quit).unwrap();
+}
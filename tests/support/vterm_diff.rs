@@ -171,7 +171,7 @@ impl<'a, 'b> VTermDiff<'a, 'b> {
 
     fn fonts(vterm: &VTerm) -> String {
         VTermDiff::scene_drawer(vterm, |cell, line| {
-            line.push_str(&(cell.attrs.underline % 10).to_string());
+            line.push_str(&(cell.attrs.font % 10).to_string());
         })
     }
 
@@ -187,7 +187,7 @@ impl<'a, 'b> VTermDiff<'a, 'b> {
 
     fn dhls(vterm: &VTerm) -> String {
         VTermDiff::scene_drawer(vterm, |cell, line| {
-            line.push_str(&(cell.attrs.underline % 10).to_string());
+            line.push_str(&(cell.attrs.dhl % 10).to_string());
         })
     }
 
@@ -312,6 +312,105 @@ mod tests {
         assert!(regex::is_match("underlines", &format!("{}", diff)).unwrap());
     }
 
+    #[test]
+    fn has_diff_when_underline_styles_are_different() {
+        let size = Size { height: 1, width: 1 };
+        let mut a = VTerm::new(&size);
+        let mut b = VTerm::new(&size);
+
+        a.write(b"\x1b[21mo").unwrap();
+        b.write(b"\x1b[4mo").unwrap();
+
+        let diff = VTermDiff::new(&a, &b);
+        assert!(diff.has_diff());
+        assert!(regex::is_match("underlines", &format!("{}", diff)).unwrap());
+    }
+
+    #[test]
+    fn has_diff_when_italics_are_different() {
+        let size = Size { height: 1, width: 1 };
+        let mut a = VTerm::new(&size);
+        let mut b = VTerm::new(&size);
+
+        a.write(b"\x1b[3mo").unwrap();
+        b.write(b"o").unwrap();
+
+        let diff = VTermDiff::new(&a, &b);
+        assert!(diff.has_diff());
+        assert!(regex::is_match("italics", &format!("{}", diff)).unwrap());
+    }
+
+    #[test]
+    fn has_diff_when_blinks_are_different() {
+        let size = Size { height: 1, width: 1 };
+        let mut a = VTerm::new(&size);
+        let mut b = VTerm::new(&size);
+
+        a.write(b"\x1b[5mo").unwrap();
+        b.write(b"o").unwrap();
+
+        let diff = VTermDiff::new(&a, &b);
+        assert!(diff.has_diff());
+        assert!(regex::is_match("blinks", &format!("{}", diff)).unwrap());
+    }
+
+    #[test]
+    fn has_diff_when_reverses_are_different() {
+        let size = Size { height: 1, width: 1 };
+        let mut a = VTerm::new(&size);
+        let mut b = VTerm::new(&size);
+
+        a.write(b"\x1b[7mo").unwrap();
+        b.write(b"o").unwrap();
+
+        let diff = VTermDiff::new(&a, &b);
+        assert!(diff.has_diff());
+        assert!(regex::is_match("reverses", &format!("{}", diff)).unwrap());
+    }
+
+    #[test]
+    fn has_diff_when_strikes_are_different() {
+        let size = Size { height: 1, width: 1 };
+        let mut a = VTerm::new(&size);
+        let mut b = VTerm::new(&size);
+
+        a.write(b"\x1b[9mo").unwrap();
+        b.write(b"o").unwrap();
+
+        let diff = VTermDiff::new(&a, &b);
+        assert!(diff.has_diff());
+        assert!(regex::is_match("strikes", &format!("{}", diff)).unwrap());
+    }
+
+    #[test]
+    fn has_diff_when_fonts_are_different() {
+        let size = Size { height: 1, width: 1 };
+        let mut a = VTerm::new(&size);
+        let mut b = VTerm::new(&size);
+
+        a.write(b"\x1b[11mo").unwrap();
+        b.write(b"o").unwrap();
+
+        let diff = VTermDiff::new(&a, &b);
+        assert!(diff.has_diff());
+        assert!(regex::is_match("fonts", &format!("{}", diff)).unwrap());
+        assert!(!regex::is_match("underlines", &format!("{}", diff)).unwrap());
+    }
+
+    #[test]
+    fn has_diff_when_double_heights_are_different() {
+        let size = Size { height: 1, width: 2 };
+        let mut a = VTerm::new(&size);
+        let mut b = VTerm::new(&size);
+
+        a.write(b"\x1b#3o").unwrap();
+        b.write(b"o").unwrap();
+
+        let diff = VTermDiff::new(&a, &b);
+        assert!(diff.has_diff());
+        assert!(regex::is_match("dhls", &format!("{}", diff)).unwrap());
+    }
+
     #[test]
     fn has_diff_when_fg_rbgs_are_different() {
         let size = Size { height: 1, width: 1 };
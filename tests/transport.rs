@@ -0,0 +1,70 @@
+use libintermix::client::ClientMsg;
+use libintermix::server::ServerMsg;
+use libintermix::transport::{self, Tx};
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::channel;
+use vterm_sys::Size;
+
+#[test]
+fn msgs_sent_on_a_socket_tx_come_out_of_the_reader() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let (tx, rx) = channel::<ClientMsg>();
+    transport::spawn_socket_reader(b, tx, None);
+
+    let socket_tx: Tx<ClientMsg> = Tx::socket(a);
+    socket_tx.send(ClientMsg::ProgramAdd {
+                 server_id: "some server".to_string(),
+                 program_id: "123".to_string(),
+                 size: Size::new(80, 24),
+             })
+             .unwrap();
+
+    match rx.recv().unwrap() {
+        ClientMsg::ProgramAdd { server_id, program_id, size } => {
+            assert_eq!(server_id, "some server");
+            assert_eq!(program_id, "123");
+            assert_eq!(size, Size::new(80, 24));
+        }
+        msg => panic!("unexpected msg {:?}", msg),
+    }
+}
+
+#[test]
+fn reader_sends_on_close_msg_when_socket_closes() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let (tx, rx) = channel::<ClientMsg>();
+    transport::spawn_socket_reader(b,
+                                   tx,
+                                   Some(ClientMsg::ServerRemove {
+                                       server_id: "some server".to_string(),
+                                   }));
+
+    drop(a);
+
+    match rx.recv().unwrap() {
+        ClientMsg::ServerRemove { server_id } => assert_eq!(server_id, "some server"),
+        msg => panic!("unexpected msg {:?}", msg),
+    }
+}
+
+#[test]
+fn subscriptions_read_off_a_socket_are_for_the_connections_client() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let (tx, rx) = channel::<ServerMsg>();
+    transport::spawn_socket_reader_with(b, tx, None, |msg: ServerMsg| msg.from_client("a"));
+
+    let socket_tx: Tx<ServerMsg> = Tx::socket(a);
+    socket_tx.send(ServerMsg::ProgramSubscribe {
+                 client_id: "b".to_string(),
+                 program_id: "123".to_string(),
+             })
+             .unwrap();
+
+    match rx.recv().unwrap() {
+        ServerMsg::ProgramSubscribe { client_id, program_id } => {
+            assert_eq!(client_id, "a");
+            assert_eq!(program_id, "123");
+        }
+        _ => panic!("unexpected msg"),
+    }
+}